@@ -0,0 +1,70 @@
+//! Decoding benchmarks comparing `Packet` with the lazy `PacketRef` view,
+//! as bulk capture processing would use them.
+//!
+//! Run with `cargo bench --bench decode`.
+
+use std::hint::black_box;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use mndp::{Neighbor, Packet, PacketRef};
+
+const PACKETS: u32 = 100_000;
+const ROUNDS: u32 = 5;
+
+// Announcements like those of a RouterOS device, each from its own address
+fn captured() -> Vec<Bytes> {
+    (0..PACKETS)
+        .map(|index| {
+            let [_, a, b, c] = index.to_be_bytes();
+            let neighbor = Neighbor::builder()
+                .mac_address([0x02, 0x4d, 0x4e, a, b, c])
+                .identity(format!("router-{}", index))
+                .platform("MikroTik")
+                .version("7.14.3 (stable)")
+                .board("CCR2004-16G-2S+")
+                .interface_name("sfp-sfpplus1")
+                .software_id("2AP7-ZVC5")
+                .ipv4_address(Ipv4Addr::from(0xc612_0000 + index))
+                .uptime(Duration::from_secs(u64::from(index) * 60))
+                .build();
+            Packet::from_neighbor(&neighbor).to_bytes()
+        })
+        .collect()
+}
+
+// Best time per packet over the rounds
+fn bench<F: FnMut(&Bytes)>(name: &str, packets: &[Bytes], mut decode: F) {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        for packet in packets {
+            decode(packet);
+        }
+        best = best.min(start.elapsed());
+    }
+    println!("{:<40} {:>8.1} ns/packet", name, best.as_nanos() as f64 / packets.len() as f64);
+}
+
+fn main() {
+    let packets = captured();
+
+    bench("Packet::to_neighbor", &packets, |bytes| {
+        black_box(Packet::from_bytes(bytes.clone()).unwrap().to_neighbor());
+    });
+    bench("Packet::to_neighbor, MAC address only", &packets, |bytes| {
+        black_box(Packet::from_bytes(bytes.clone()).unwrap().to_neighbor().mac_address);
+    });
+    bench("PacketRef, MAC address only", &packets, |bytes| {
+        black_box(PacketRef::from_bytes(bytes).unwrap().neighbor().mac_address());
+    });
+    bench("PacketRef, MAC address and identity", &packets, |bytes| {
+        let neighbor = PacketRef::from_bytes(bytes).unwrap().neighbor();
+        black_box((neighbor.mac_address(), neighbor.identity()));
+    });
+    bench("PacketRef::get, MAC address only", &packets, |bytes| {
+        let packet = PacketRef::from_bytes(bytes).unwrap();
+        black_box(packet.get(mndp::MndpType::MacAddress).and_then(|field| field.as_mac().ok()));
+    });
+}
@@ -0,0 +1,16 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Generate the gRPC service only when it is enabled, so the default
+    // build has no code generation step
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/mndp.proto");
+        let descriptors = protox::compile(["proto/mndp.proto"], ["proto"])
+            .expect("failed to parse proto/mndp.proto");
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)
+            .expect("failed to generate gRPC service");
+    }
+}
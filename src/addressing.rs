@@ -0,0 +1,211 @@
+//! Destination addresses for sending MNDP packets, for senders built on the
+//! lower-level API (`Packet` and plain sockets).
+
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+use macaddr::MacAddr6;
+
+use crate::{Neighbor, MNDP_IPV6_GROUP};
+
+/// Subnet broadcast address of `addr` with `netmask`; e.g. 192.168.88.255
+/// for 192.168.88.1/255.255.255.0.
+pub fn ipv4_subnet_broadcast(addr: Ipv4Addr, netmask: Ipv4Addr) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(addr) | !u32::from(netmask))
+}
+
+/// IPv4 destination for sending on all interfaces (or an interface the
+/// socket is bound to): the limited broadcast address, 255.255.255.255.
+pub fn ipv4_destination(port: u16) -> SocketAddrV4 {
+    SocketAddrV4::new(Ipv4Addr::BROADCAST, port)
+}
+
+/// IPv6 destination for sending on the interface with index `scope_id`: the
+/// link-local MNDP multicast group with the interface as its zone. A scope
+/// ID of 0 leaves the choice of interface to the operating system.
+pub fn ipv6_destination(port: u16, scope_id: u32) -> SocketAddrV6 {
+    SocketAddrV6::new(MNDP_IPV6_GROUP, port, 0, scope_id)
+}
+
+/// Look up the index of a network interface by name; e.g. for the scope ID
+/// of `ipv6_destination`.
+pub fn interface_index(interface: &str) -> io::Result<u32> {
+    let name = CString::new(interface)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::last_os_error()),
+        index => Ok(index),
+    }
+}
+
+/// Look up the name of a network interface by index; e.g. for the
+/// `DiscoveredNeighbor::scope_id` of the link a neighbor was heard on.
+pub fn interface_name(index: u32) -> io::Result<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    if unsafe { libc::if_indextoname(index, name.as_mut_ptr()) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+/// IPv4 subnet broadcast destinations of a network interface, one for each
+/// of its IPv4 addresses, on `port`. Sending to these reaches the
+/// interface's subnets even from a socket which is not bound to it.
+pub fn interface_broadcasts(interface: &str, port: u16) -> io::Result<Vec<SocketAddrV4>> {
+    interface_index(interface)?;
+
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut broadcasts = Vec::new();
+    let mut next = addrs;
+    while let Some(ifa) = unsafe { next.as_ref() } {
+        next = ifa.ifa_next;
+
+        let name = unsafe { CStr::from_ptr(ifa.ifa_name) };
+        if name.to_bytes() != interface.as_bytes() {
+            continue;
+        }
+        if let (Some(addr), Some(netmask)) = unsafe { (ipv4(ifa.ifa_addr), ipv4(ifa.ifa_netmask)) } {
+            broadcasts.push(SocketAddrV4::new(ipv4_subnet_broadcast(addr, netmask), port));
+        }
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(broadcasts)
+}
+
+/// Local network interfaces which can be announced on (up, capable of
+/// broadcast and not loopback), each described by the `Neighbor` fields
+/// particular to it: its name, MAC address, first IPv4 address and an IPv6
+/// address, global if it has one.
+pub fn local_interfaces() -> io::Result<Vec<Neighbor>> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Each interface has an entry per address, in no particular order
+    let mut interfaces: Vec<Neighbor> = Vec::new();
+    let mut next = addrs;
+    while let Some(ifa) = unsafe { next.as_ref() } {
+        next = ifa.ifa_next;
+
+        let flags = ifa.ifa_flags as libc::c_int;
+        if flags & libc::IFF_UP == 0 || flags & libc::IFF_BROADCAST == 0 || flags & libc::IFF_LOOPBACK != 0 {
+            continue;
+        }
+        let name = unsafe { CStr::from_ptr(ifa.ifa_name) }.to_string_lossy();
+        let interface = match interfaces.iter().position(|interface| interface.interface_name.as_deref() == Some(&name)) {
+            Some(index) => &mut interfaces[index],
+            None => {
+                interfaces.push(Neighbor::builder().interface_name(name.into_owned()).build());
+                interfaces.last_mut().unwrap()
+            }
+        };
+
+        if let Some(addr) = unsafe { ipv4(ifa.ifa_addr) } {
+            interface.ipv4_address.get_or_insert(addr);
+        } else if let Some(addr) = unsafe { ipv6(ifa.ifa_addr) } {
+            let link_local = |addr: &Ipv6Addr| addr.segments()[0] & 0xffc0 == 0xfe80;
+            if interface.ipv6_address.is_none_or(|current| link_local(&current) && !link_local(&addr)) {
+                interface.ipv6_address = Some(addr);
+            }
+        } else if let Some(mac) = unsafe { link_mac(ifa.ifa_addr) } {
+            interface.mac_address = Some(mac);
+        }
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(interfaces)
+}
+
+// IPv4 address of a socket address from getifaddrs, if it is one
+unsafe fn ipv4(addr: *const libc::sockaddr) -> Option<Ipv4Addr> {
+    match addr.as_ref() {
+        Some(addr) if addr.sa_family as libc::c_int == libc::AF_INET => {
+            let addr = &*(addr as *const libc::sockaddr as *const libc::sockaddr_in);
+            Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)))
+        }
+        _ => None,
+    }
+}
+
+// IPv6 address of a socket address from getifaddrs, if it is one
+unsafe fn ipv6(addr: *const libc::sockaddr) -> Option<Ipv6Addr> {
+    match addr.as_ref() {
+        Some(addr) if addr.sa_family as libc::c_int == libc::AF_INET6 => {
+            let addr = &*(addr as *const libc::sockaddr as *const libc::sockaddr_in6);
+            Some(Ipv6Addr::from(addr.sin6_addr.s6_addr))
+        }
+        _ => None,
+    }
+}
+
+// Hardware address of a link-layer socket address from getifaddrs, if it is
+// an Ethernet-style one (all zeroes for interfaces without one)
+#[cfg(target_os = "linux")]
+unsafe fn link_mac(addr: *const libc::sockaddr) -> Option<MacAddr6> {
+    match addr.as_ref() {
+        Some(addr) if addr.sa_family as libc::c_int == libc::AF_PACKET => {
+            let addr = &*(addr as *const libc::sockaddr as *const libc::sockaddr_ll);
+            let mac = <[u8; 6]>::try_from(&addr.sll_addr[..6]).ok()?;
+            match addr.sll_halen == 6 && mac != [0; 6] {
+                true => Some(MacAddr6::from(mac)),
+                false => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+unsafe fn link_mac(addr: *const libc::sockaddr) -> Option<MacAddr6> {
+    match addr.as_ref() {
+        Some(addr) if addr.sa_family as libc::c_int == libc::AF_LINK => {
+            let addr = &*(addr as *const libc::sockaddr as *const libc::sockaddr_dl);
+            let data = std::slice::from_raw_parts(addr.sdl_data.as_ptr() as *const u8, addr.sdl_data.len());
+            let start = addr.sdl_nlen as usize;
+            let mac = <[u8; 6]>::try_from(data.get(start..start + 6)?).ok()?;
+            match addr.sdl_alen == 6 && mac != [0; 6] {
+                true => Some(MacAddr6::from(mac)),
+                false => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+unsafe fn link_mac(_addr: *const libc::sockaddr) -> Option<MacAddr6> {
+    None
+}
+
+#[test]
+fn test_addressing() {
+    use crate::MNDP_PORT;
+
+    let addr = Ipv4Addr::new(192, 168, 88, 1);
+    assert_eq!(ipv4_subnet_broadcast(addr, Ipv4Addr::new(255, 255, 255, 0)), Ipv4Addr::new(192, 168, 88, 255));
+    assert_eq!(ipv4_subnet_broadcast(addr, Ipv4Addr::new(255, 255, 255, 255)), addr);
+    assert_eq!(ipv4_destination(MNDP_PORT).to_string(), "255.255.255.255:5678");
+    assert_eq!(ipv6_destination(MNDP_PORT, 2).scope_id(), 2);
+
+    // Loopback has 127.0.0.1/8 on Linux and macOS, but its name varies
+    if let Ok(broadcasts) = interface_broadcasts("lo", MNDP_PORT) {
+        assert!(broadcasts.contains(&SocketAddrV4::new(Ipv4Addr::new(127, 255, 255, 255), MNDP_PORT)));
+    }
+    assert!(interface_broadcasts("no-such-interface", MNDP_PORT).is_err());
+
+    // Every interface is named, and loopback is never one of them
+    for interface in local_interfaces().unwrap() {
+        let name = interface.interface_name.unwrap();
+        assert_eq!(interface_name(interface_index(&name).unwrap()).unwrap(), name);
+        assert!(name != "lo" && name != "lo0");
+        assert!(interface.mac_address.is_none_or(|mac| !mac.is_nil()));
+    }
+}
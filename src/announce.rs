@@ -0,0 +1,524 @@
+//! Announcer which advertises the local host as an MNDP neighbor.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+#[cfg(all(feature = "avahi", target_os = "linux"))]
+use crate::AvahiPublisher;
+use crate::discovery::{broadcast_all, default_transports, recv_backoff, POLL_INTERVAL};
+use crate::{local_interfaces, AddressFamily, Neighbor, Packet, RateLimiter, Transport, DEFAULT_MAX_PACKET_SIZE, MNDP_PORT, SOLICIT};
+
+/// Interval between announcements; RouterOS announces roughly every 60 seconds.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Transports announcing the same description; those of one interface, or
+// those not bound to one
+struct Outlet {
+    interface: Option<String>,
+    neighbor: Neighbor,
+    transports: Vec<Box<dyn Transport>>,
+}
+
+// State shared between the announcer handle and its threads
+struct Shared {
+    outlets: Vec<Outlet>,
+    neighbor: Neighbor,
+    sequence: AtomicU16,
+    max_packet_size: usize,
+    limiter: RateLimiter,
+    interval: Duration,
+    jitter: Duration,
+    immediate: bool,
+    running: AtomicBool,
+}
+
+impl Shared {
+    // Announce on every outlet with the same sequence number; succeeds if
+    // sent on at least one transport
+    fn announce(&self) -> io::Result<()> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let mut result = Err(io::Error::new(io::ErrorKind::NotConnected, "no transports"));
+        for outlet in &self.outlets {
+            let sent = self.announce_on(outlet, sequence);
+            if result.is_err() {
+                result = sent;
+            }
+        }
+        result
+    }
+
+    fn announce_on(&self, outlet: &Outlet, sequence: u16) -> io::Result<()> {
+        let mut packet = Packet::from_neighbor(&outlet.neighbor);
+        packet.set_sequence(sequence);
+        let bytes: Bytes = packet.to_bytes_with_limit(self.max_packet_size);
+        broadcast_all(outlet.transports.iter().map(|transport| transport.as_ref()), &bytes)
+    }
+
+    // Reply to solicitations received on `transport`, announcing only on
+    // the outlet it belongs to, so the rest of the host's interfaces are
+    // neither flooded nor described on links which did not ask
+    fn receive(&self, outlet: &Outlet, transport: &dyn Transport) {
+        let mut buf = [0u8; 64];
+
+        while self.running.load(Ordering::Relaxed) {
+            let len = match transport.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(e) => {
+                    recv_backoff(&e);
+                    continue;
+                }
+            };
+
+            if Packet::from_bytes(Bytes::copy_from_slice(&buf[..len])) == Ok(SOLICIT) && self.limiter.try_acquire() {
+                let _ = self.announce_on(outlet, self.sequence.fetch_add(1, Ordering::Relaxed));
+            }
+        }
+    }
+
+    fn periodic(&self) {
+        let mut next = Instant::now();
+        if !self.immediate {
+            next += jittered(self.interval, self.jitter);
+        }
+        while self.running.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= next {
+                if self.limiter.try_acquire() {
+                    let _ = self.announce();
+                }
+                // Jitter each interval, but keep to the average rate
+                next += jittered(self.interval, self.jitter);
+                if next < now {
+                    next = now + self.interval;
+                }
+            }
+            thread::sleep(next.saturating_duration_since(Instant::now()).min(POLL_INTERVAL));
+        }
+    }
+}
+
+// `interval` varied uniformly by up to `jitter` either way
+pub(crate) fn jittered(interval: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return interval;
+    }
+    // Randomly seeded hash, to avoid a dependency on a random number crate
+    let random = RandomState::new().build_hasher().finish();
+    let offset = jitter.mul_f64(random as f64 / u64::MAX as f64 * 2.0);
+    (interval + offset).saturating_sub(jitter)
+}
+
+/// Running announcer, which broadcasts a `Neighbor` description of the local
+/// host periodically, and in reply to solicitations on the interface they
+/// were received on. The announcer stops when it is dropped.
+///
+/// With the default transports, the announcer opens them on each interface
+/// it can announce on (see `local_interfaces`), and describes the host on
+/// each with that interface's MAC address, IP addresses and name wherever
+/// the announced neighbor leaves them unset, as RouterOS does.
+pub struct Announcer {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+    #[cfg(all(feature = "avahi", target_os = "linux"))]
+    _avahi: Option<AvahiPublisher>,
+}
+
+/// Builder structure for an `Announcer`.
+pub struct AnnouncerBuilder {
+    neighbor: Neighbor,
+    port: u16,
+    family: AddressFamily,
+    interfaces: Vec<String>,
+    namespace: Option<String>,
+    max_packet_size: usize,
+    limiter: RateLimiter,
+    interval: Duration,
+    jitter: Duration,
+    immediate: bool,
+    transports: Vec<Box<dyn Transport>>,
+    overrides: HashMap<String, Neighbor>,
+    #[cfg(all(feature = "avahi", target_os = "linux"))]
+    avahi: bool,
+}
+
+impl Announcer {
+    /// Create a new announcer builder which will announce `neighbor`.
+    pub fn builder(neighbor: Neighbor) -> AnnouncerBuilder {
+        AnnouncerBuilder {
+            neighbor,
+            port: MNDP_PORT,
+            family: AddressFamily::Any,
+            interfaces: Vec::new(),
+            namespace: None,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            limiter: RateLimiter::default(),
+            interval: ANNOUNCE_INTERVAL,
+            jitter: Duration::ZERO,
+            immediate: true,
+            transports: Vec::new(),
+            overrides: HashMap::new(),
+            #[cfg(all(feature = "avahi", target_os = "linux"))]
+            avahi: false,
+        }
+    }
+
+    /// The neighbor description being announced, before any per-interface
+    /// fields are filled in.
+    pub fn neighbor(&self) -> &Neighbor {
+        &self.shared.neighbor
+    }
+
+    /// The description announced on each interface, or with `None` on
+    /// transports not bound to one.
+    pub fn announced(&self) -> impl Iterator<Item = (Option<&str>, &Neighbor)> {
+        self.shared.outlets.iter().map(|outlet| (outlet.interface.as_deref(), &outlet.neighbor))
+    }
+
+    /// Send an announcement now, in addition to the periodic announcements.
+    pub fn announce(&self) -> io::Result<()> {
+        self.shared.announce()
+    }
+}
+
+impl Drop for Announcer {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl AnnouncerBuilder {
+    /// Set the UDP port for the default transports (default `MNDP_PORT`).
+    pub fn port(mut self, value: u16) -> Self {
+        self.port = value;
+        self
+    }
+
+    /// Restrict the default transports to one address family (default
+    /// `AddressFamily::Any`).
+    pub fn address_family(mut self, value: AddressFamily) -> Self {
+        self.family = value;
+        self
+    }
+
+    /// Announce only on the named network interface; e.g. 'ether1'. May be
+    /// called more than once (default all interfaces).
+    pub fn interface<S: Into<String>>(mut self, value: S) -> Self {
+        self.interfaces.push(value.into());
+        self
+    }
+
+    /// Open the default transports in a Linux network namespace, given by
+    /// name or path (see `in_namespace`), instead of the current one.
+    #[cfg(target_os = "linux")]
+    pub fn namespace<S: Into<String>>(mut self, value: S) -> Self {
+        self.namespace = Some(value.into());
+        self
+    }
+
+    /// Set the largest announcement sent; fields which do not fit are left
+    /// out (default `DEFAULT_MAX_PACKET_SIZE`).
+    pub fn max_packet_size(mut self, value: usize) -> Self {
+        self.max_packet_size = value;
+        self
+    }
+
+    /// Set the interval between periodic announcements, which must not be
+    /// zero (default `ANNOUNCE_INTERVAL`).
+    pub fn interval(mut self, value: Duration) -> Self {
+        self.interval = value;
+        self
+    }
+
+    /// Vary each interval randomly by up to this much either way, so
+    /// announcers started together do not stay in step (default none).
+    pub fn jitter(mut self, value: Duration) -> Self {
+        self.jitter = value;
+        self
+    }
+
+    /// Announce as soon as the announcer starts, rather than after the first
+    /// interval (default `true`).
+    pub fn immediate(mut self, value: bool) -> Self {
+        self.immediate = value;
+        self
+    }
+
+    /// Limit the rate of periodic announcements and replies to solicitations
+    /// combined; solicitations beyond it are ignored (default
+    /// `RateLimiter::default()`). Explicit `Announcer::announce` calls are
+    /// not limited.
+    pub fn rate_limit(mut self, value: RateLimiter) -> Self {
+        self.limiter = value;
+        self
+    }
+
+    /// Use a custom transport. If any are given, the default UDP transports
+    /// are not created, and the neighbor is announced on them as given.
+    pub fn transport<T: Transport + 'static>(mut self, value: T) -> Self {
+        self.transports.push(Box::new(value));
+        self
+    }
+
+    /// On `interface`, announce the fields set in `fields` in place of both
+    /// the announced neighbor's and the interface's own; e.g. a different
+    /// identity per interface.
+    pub fn interface_neighbor<S: Into<String>>(mut self, interface: S, fields: Neighbor) -> Self {
+        self.overrides.insert(interface.into(), fields);
+        self
+    }
+
+    /// Also register the announced identity with Avahi, so it is visible
+    /// over mDNS/DNS-SD (default `false`).
+    #[cfg(all(feature = "avahi", target_os = "linux"))]
+    pub fn avahi(mut self, value: bool) -> Self {
+        self.avahi = value;
+        self
+    }
+
+    /// Open the transports and start announcing.
+    pub fn start(self) -> io::Result<Announcer> {
+        if self.interval.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "announce interval must not be zero"));
+        }
+        #[cfg(all(feature = "avahi", target_os = "linux"))]
+        let avahi = match self.avahi {
+            true => Some(AvahiPublisher::publish(&self.neighbor).map_err(|e| io::Error::other(e.to_string()))?),
+            false => None,
+        };
+
+        let outlets = match self.transports.is_empty() {
+            true => self.interface_outlets()?,
+            false => vec![Outlet { interface: None, neighbor: self.neighbor.clone(), transports: self.transports }],
+        };
+
+        let shared = Arc::new(Shared {
+            outlets,
+            neighbor: self.neighbor,
+            sequence: AtomicU16::new(0),
+            max_packet_size: self.max_packet_size,
+            limiter: self.limiter,
+            interval: self.interval,
+            jitter: self.jitter,
+            immediate: self.immediate,
+            running: AtomicBool::new(true),
+        });
+
+        let mut threads = Vec::new();
+        for (outlet, outlet_ref) in shared.outlets.iter().enumerate() {
+            for index in 0..outlet_ref.transports.len() {
+                let shared = shared.clone();
+                threads.push(thread::spawn(move || {
+                    let outlet = &shared.outlets[outlet];
+                    shared.receive(outlet, outlet.transports[index].as_ref())
+                }));
+            }
+        }
+        let periodic = shared.clone();
+        threads.push(thread::spawn(move || periodic.periodic()));
+
+        Ok(Announcer {
+            shared,
+            threads,
+            #[cfg(all(feature = "avahi", target_os = "linux"))]
+            _avahi: avahi,
+        })
+    }
+
+    // Default transports on each interface announced on, falling back to
+    // unbound ones if there are no such interfaces or none can be bound to
+    fn interface_outlets(&self) -> io::Result<Vec<Outlet>> {
+        let local = self.local_interfaces().unwrap_or_default();
+        let interfaces: Vec<Neighbor> = match self.interfaces.is_empty() {
+            true => local,
+            false => self.interfaces.iter().map(|name| {
+                local.iter()
+                    .find(|interface| interface.interface_name.as_ref() == Some(name))
+                    .cloned()
+                    .unwrap_or_else(|| Neighbor::builder().interface_name(name.clone()).build())
+            }).collect(),
+        };
+
+        let mut outlets = Vec::new();
+        let mut error = None;
+        for fields in interfaces {
+            let name = fields.interface_name.clone().unwrap_or_default();
+            let transports = match default_transports(self.port, self.family, Some(&name), self.namespace.as_deref()) {
+                Ok(transports) => transports,
+                // Only named interfaces are required to open
+                Err(e) if self.interfaces.is_empty() => {
+                    error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // The announced neighbor's fields take precedence over the
+            // interface's, and overrides over both
+            let mut neighbor = fields;
+            neighbor.merge(&self.neighbor);
+            if let Some(fields) = self.overrides.get(&name) {
+                neighbor.merge(fields);
+            }
+            outlets.push(Outlet { interface: Some(name), neighbor, transports });
+        }
+
+        if outlets.is_empty() {
+            let transports = default_transports(self.port, self.family, None, self.namespace.as_deref())
+                .map_err(|e| error.unwrap_or(e))?;
+            outlets.push(Outlet { interface: None, neighbor: self.neighbor.clone(), transports });
+        }
+        Ok(outlets)
+    }
+
+    // Interfaces of the namespace the transports are opened in
+    fn local_interfaces(&self) -> io::Result<Vec<Neighbor>> {
+        #[cfg(target_os = "linux")]
+        if let Some(namespace) = &self.namespace {
+            return crate::netns::in_namespace(namespace, local_interfaces);
+        }
+        local_interfaces()
+    }
+}
+
+#[test]
+fn test_announcer_solicit() {
+    use std::net::UdpSocket;
+    use crate::UdpTransport;
+
+    // Announcer broadcasts to the peer socket and receives from it
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(POLL_INTERVAL)).unwrap();
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .identity("host1")
+        .build();
+    let _announcer = Announcer::builder(neighbor.clone())
+        .transport(UdpTransport::new(socket.try_clone().unwrap(), peer.local_addr().unwrap()))
+        .start()
+        .unwrap();
+
+    // Initial announcement
+    let mut buf = [0u8; 1500];
+    let len = peer.recv(&mut buf).unwrap();
+    let packet = Packet::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+    assert_eq!(packet.to_neighbor(), neighbor);
+    assert_eq!(packet.sequence(), 0);
+
+    // Reply to a solicitation
+    let bytes: Bytes = SOLICIT.to_bytes();
+    peer.send_to(&bytes, socket.local_addr().unwrap()).unwrap();
+    let len = peer.recv(&mut buf).unwrap();
+    let packet = Packet::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+    assert_eq!(packet.sequence(), 1);
+}
+
+#[test]
+fn test_announcer_reply_outlet() {
+    use std::net::UdpSocket;
+    use crate::UdpTransport;
+
+    // Two outlets, each broadcasting to its own peer
+    let mut sockets = Vec::new();
+    let mut outlets = Vec::new();
+    for name in ["ether1", "ether2"] {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(POLL_INTERVAL)).unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let transport = UdpTransport::new(socket.try_clone().unwrap(), peer.local_addr().unwrap());
+        outlets.push(Outlet {
+            interface: Some(String::from(name)),
+            neighbor: Neighbor::builder().interface_name(name).build(),
+            transports: vec![Box::new(transport)],
+        });
+        sockets.push((socket, peer));
+    }
+    let shared = Arc::new(Shared {
+        outlets,
+        neighbor: Neighbor::default(),
+        sequence: AtomicU16::new(0),
+        max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+        limiter: RateLimiter::default(),
+        interval: ANNOUNCE_INTERVAL,
+        jitter: Duration::ZERO,
+        immediate: false,
+        running: AtomicBool::new(true),
+    });
+    let receiver = shared.clone();
+    let thread = thread::spawn(move || receiver.receive(&receiver.outlets[1], receiver.outlets[1].transports[0].as_ref()));
+
+    // A solicitation on the second outlet is answered there only
+    let bytes: Bytes = SOLICIT.to_bytes();
+    let (socket, peer) = &sockets[1];
+    peer.send_to(&bytes, socket.local_addr().unwrap()).unwrap();
+    let mut buf = [0u8; 1500];
+    let len = peer.recv(&mut buf).unwrap();
+    let packet = Packet::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+    assert_eq!(packet.to_neighbor().interface_name.as_deref(), Some("ether2"));
+    assert!(sockets[0].1.recv(&mut buf).is_err());
+
+    shared.running.store(false, Ordering::Relaxed);
+    thread.join().unwrap();
+}
+
+#[test]
+fn test_announcer_interfaces() {
+    let neighbor = Neighbor::builder().identity("host1").platform("Linux").build();
+    let interfaces = local_interfaces().unwrap();
+    let mut builder = Announcer::builder(neighbor).port(0).immediate(false);
+    for interface in &interfaces {
+        let name = interface.interface_name.clone().unwrap();
+        builder = builder.interface_neighbor(name, Neighbor::builder().identity("host1-override").build());
+    }
+
+    // Binding to interfaces may be refused, leaving unbound transports
+    let announcer = builder.start().unwrap();
+    for (name, announced) in announcer.announced() {
+        assert_eq!(announced.platform.as_deref(), Some("Linux"));
+        match name {
+            Some(name) => {
+                let own = interfaces.iter().find(|interface| interface.interface_name.as_deref() == Some(name)).unwrap();
+                assert_eq!(announced.identity.as_deref(), Some("host1-override"));
+                assert_eq!(announced.interface_name.as_deref(), Some(name));
+                assert_eq!(announced.mac_address, own.mac_address);
+                assert_eq!(announced.ipv4_address, own.ipv4_address);
+            }
+            None => assert_eq!(announced, announcer.neighbor()),
+        }
+    }
+}
+
+#[test]
+fn test_announcer_zero_interval() {
+    use crate::MockTransport;
+
+    let builder = Announcer::builder(Neighbor::builder().identity("host1").build())
+        .interval(Duration::ZERO)
+        .transport(MockTransport::new());
+    assert_eq!(builder.start().err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+}
+
+#[test]
+fn test_jittered() {
+    let interval = Duration::from_secs(60);
+    assert_eq!(jittered(interval, Duration::ZERO), interval);
+    for _ in 0..100 {
+        let value = jittered(interval, Duration::from_secs(5));
+        assert!(value >= Duration::from_secs(55) && value <= Duration::from_secs(65), "{:?}", value);
+    }
+    assert!(jittered(Duration::from_secs(1), Duration::from_secs(5)) <= Duration::from_secs(6));
+}
@@ -0,0 +1,103 @@
+//! Publication of the announced identity via Avahi (mDNS/DNS-SD) on Linux.
+
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::{Neighbor, MNDP_PORT};
+
+/// DNS-SD service type registered for the announced host.
+pub const AVAHI_SERVICE_TYPE: &str = "_mndp._udp";
+
+// Avahi wildcard interface and protocol
+const AVAHI_IF_UNSPEC: i32 = -1;
+const AVAHI_PROTO_UNSPEC: i32 = -1;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Avahi.Server",
+    default_service = "org.freedesktop.Avahi",
+    default_path = "/"
+)]
+trait AvahiServer {
+    fn entry_group_new(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Avahi.EntryGroup",
+    default_service = "org.freedesktop.Avahi"
+)]
+trait EntryGroup {
+    #[allow(clippy::too_many_arguments)]
+    fn add_service(
+        &self,
+        interface: i32,
+        protocol: i32,
+        flags: u32,
+        name: &str,
+        service_type: &str,
+        domain: &str,
+        host: &str,
+        port: u16,
+        txt: Vec<Vec<u8>>,
+    ) -> zbus::Result<()>;
+
+    fn commit(&self) -> zbus::Result<()>;
+
+    fn free(&self) -> zbus::Result<()>;
+}
+
+/// DNS-SD registration of an announced `Neighbor`, named after its identity
+/// with the other fields as TXT records. The registration is withdrawn when
+/// this is dropped.
+pub struct AvahiPublisher {
+    group: EntryGroupProxyBlocking<'static>,
+}
+
+impl AvahiPublisher {
+    /// Register `neighbor` with the Avahi daemon on the system bus.
+    pub fn publish(neighbor: &Neighbor) -> zbus::Result<AvahiPublisher> {
+        let connection = Connection::system()?;
+        let path = AvahiServerProxyBlocking::new(&connection)?.entry_group_new()?;
+        let group = EntryGroupProxyBlocking::builder(&connection)
+            .path(path)?
+            .build()?;
+
+        let name = neighbor.identity.as_deref().unwrap_or("MikroTik");
+        group.add_service(AVAHI_IF_UNSPEC, AVAHI_PROTO_UNSPEC, 0, name, AVAHI_SERVICE_TYPE,
+            "", "", MNDP_PORT, txt_records(neighbor))?;
+        group.commit()?;
+
+        Ok(AvahiPublisher { group })
+    }
+}
+
+impl Drop for AvahiPublisher {
+    fn drop(&mut self) {
+        let _ = self.group.free();
+    }
+}
+
+fn txt_records(neighbor: &Neighbor) -> Vec<Vec<u8>> {
+    let fields = [
+        ("board", neighbor.board.clone()),
+        ("version", neighbor.version.clone()),
+        ("platform", neighbor.platform.clone()),
+        ("software-id", neighbor.software_id.clone()),
+        ("mac", neighbor.mac_address.map(|m| m.to_string())),
+    ];
+    fields.iter()
+        .filter_map(|(key, value)| value.as_ref().map(|v| format!("{}={}", key, v).into_bytes()))
+        .collect()
+}
+
+#[test]
+fn test_avahi_txt_records() {
+    let neighbor = Neighbor::builder()
+        .identity("host1")
+        .board("RB760iGS")
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .build();
+    assert_eq!(txt_records(&neighbor), vec![
+        b"board=RB760iGS".to_vec(),
+        b"mac=00:00:00:00:00:01".to_vec(),
+    ]);
+}
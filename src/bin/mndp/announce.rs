@@ -0,0 +1,150 @@
+//! `mndp announce`: advertise this host as an MNDP neighbor.
+
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use clap::Args;
+use mndp::macaddr::MacAddr6;
+use mndp::{Announcer, Neighbor, RateLimiter};
+
+use crate::{invalid_data, parse_secs, Global};
+
+#[derive(Args)]
+pub struct AnnounceArgs {
+    /// JSON neighbor description to start from; other options override it
+    #[arg(long)]
+    file: Option<PathBuf>,
+    /// Identity (default the hostname)
+    #[arg(long)]
+    identity: Option<String>,
+    /// MAC address
+    #[arg(long)]
+    mac_address: Option<MacAddr6>,
+    /// IPv4 address
+    #[arg(long)]
+    ipv4_address: Option<Ipv4Addr>,
+    /// IPv6 address
+    #[arg(long)]
+    ipv6_address: Option<Ipv6Addr>,
+    /// Interface name
+    #[arg(long)]
+    interface_name: Option<String>,
+    /// Platform
+    #[arg(long)]
+    platform: Option<String>,
+    /// Software version
+    #[arg(long)]
+    version: Option<String>,
+    /// Board name
+    #[arg(long)]
+    board: Option<String>,
+    /// Software ID
+    #[arg(long)]
+    software_id: Option<String>,
+    /// Interval between announcements, in seconds
+    #[arg(long, default_value = "60", value_parser = parse_secs)]
+    interval: Duration,
+    /// Vary each interval randomly by up to this many seconds either way
+    #[arg(long, default_value = "0", value_parser = parse_secs)]
+    jitter: Duration,
+    /// Wait for the first interval before announcing
+    #[arg(long)]
+    delay_start: bool,
+    /// Most announcements sent at once, including replies to solicitations
+    #[arg(long, default_value_t = 10)]
+    burst: u32,
+    /// Time in which one more announcement is allowed after a burst, in
+    /// seconds
+    #[arg(long, default_value = "1", value_parser = parse_secs)]
+    refill: Duration,
+    /// Also publish the identity via Avahi (mDNS/DNS-SD)
+    #[cfg(all(feature = "avahi", target_os = "linux"))]
+    #[arg(long)]
+    avahi: bool,
+}
+
+/// Name of this host, as announced by default.
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    match unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } {
+        0 => {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+        }
+        _ => None,
+    }
+}
+
+// Build the neighbor description from the file and options
+fn neighbor(args: &AnnounceArgs) -> io::Result<Neighbor> {
+    let base = match &args.file {
+        Some(path) => Neighbor::from_json_str(&fs::read_to_string(path)?)
+            .map_err(|e| invalid_data(format!("invalid neighbor JSON: {}", e)))?,
+        None => Neighbor::new(),
+    };
+    let mut neighbor = Neighbor::builder();
+    neighbor = match args.identity.clone().or(base.identity).or_else(hostname) {
+        Some(identity) => neighbor.identity(identity),
+        None => neighbor,
+    };
+
+    macro_rules! set {
+        ($field:ident) => {
+            if let Some(value) = args.$field.clone().or(base.$field) {
+                neighbor = neighbor.$field(value);
+            }
+        };
+    }
+    set!(mac_address);
+    set!(ipv4_address);
+    set!(ipv6_address);
+    set!(interface_name);
+    set!(platform);
+    set!(version);
+    set!(board);
+    set!(software_id);
+    if let Some(unpack) = base.unpack {
+        neighbor = neighbor.unpack(unpack);
+    }
+
+    Ok(neighbor.build())
+}
+
+pub fn announce(global: &Global, args: &AnnounceArgs) -> io::Result<bool> {
+    let neighbor = neighbor(args)?;
+
+    let mut builder = Announcer::builder(neighbor)
+        .port(global.port)
+        .address_family(global.family())
+        .max_packet_size(global.max_packet_size)
+        .interval(args.interval)
+        .jitter(args.jitter)
+        .immediate(!args.delay_start)
+        .rate_limit(RateLimiter::new(args.burst, args.refill));
+    for interface in &global.interfaces {
+        builder = builder.interface(interface.clone());
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(namespace) = &global.netns {
+        builder = builder.namespace(namespace.clone());
+    }
+    #[cfg(all(feature = "avahi", target_os = "linux"))]
+    {
+        builder = builder.avahi(args.avahi);
+    }
+
+    let announcer = builder.start()?;
+    if !global.quiet {
+        eprintln!("announcing until interrupted");
+        for (interface, neighbor) in announcer.announced() {
+            eprint!("\non {}:\n{}", interface.unwrap_or("all interfaces"), neighbor);
+        }
+    }
+    loop {
+        thread::park();
+    }
+}
@@ -0,0 +1,181 @@
+//! `mndp check`: monitoring plugin reporting whether a device is visible,
+//! with Nagios/Icinga exit codes and perfdata.
+
+use std::fmt::Write as _;
+use std::io;
+use std::process;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use mndp::macaddr::MacAddr6;
+use mndp::{sanitize, DiscoveredNeighbor};
+
+use crate::{parse_secs, Global};
+
+// Plugin exit codes
+const OK: i32 = 0;
+const WARNING: i32 = 1;
+const CRITICAL: i32 = 2;
+const UNKNOWN: i32 = 3;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// MAC address of the device which must be visible
+    #[arg(long, value_name = "MAC")]
+    expect_mac: MacAddr6,
+    /// Warn if the device runs an older version; e.g. 7.12
+    #[arg(long, value_name = "VERSION")]
+    min_version: Option<String>,
+    /// Time to wait for the device to reply, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs)]
+    timeout: Duration,
+}
+
+// Numeric components of a version; e.g. [7, 12, 1] for '7.12.1 (stable)'
+fn version_numbers(version: &str) -> Option<Vec<u64>> {
+    let numeric = version.split_whitespace().next()?;
+    numeric.split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+// Whether version `actual` is older than `minimum`, missing trailing
+// components counting as zero; e.g. 7.12 is the same as 7.12.0
+fn older(actual: &[u64], minimum: &[u64]) -> bool {
+    let len = actual.len().max(minimum.len());
+    let padded = |numbers: &[u64]| -> Vec<u64> { numbers.iter().copied().chain(std::iter::repeat(0)).take(len).collect() };
+    padded(actual) < padded(minimum)
+}
+
+// Solicit and wait until the device is heard from or the timeout passes,
+// returning it with the time it took to reply
+fn find(global: &Global, args: &CheckArgs) -> io::Result<Option<(DiscoveredNeighbor, Duration)>> {
+    let discovery = global.discovery()?.start()?;
+    let events = discovery.subscribe();
+    discovery.solicit()?;
+
+    let start = Instant::now();
+    loop {
+        if let Some(neighbor) = discovery.neighbor(&args.expect_mac) {
+            return Ok(Some((neighbor, start.elapsed())));
+        }
+        match args.timeout.checked_sub(start.elapsed()) {
+            Some(remaining) if events.recv_timeout(remaining).is_ok() => {}
+            _ => return Ok(None),
+        }
+    }
+}
+
+// Status and message for the device, if it was found
+fn status(args: &CheckArgs, found: Option<&DiscoveredNeighbor>) -> (i32, String) {
+    let discovered = match found {
+        Some(discovered) => discovered,
+        None => return (CRITICAL, format!("{} not seen", args.expect_mac)),
+    };
+    let neighbor = &discovered.neighbor;
+    let name = match &neighbor.identity {
+        Some(identity) => format!("{} ({})", sanitize(identity), args.expect_mac),
+        None => args.expect_mac.to_string(),
+    };
+    let version = neighbor.version.as_deref().map(sanitize);
+
+    if let Some(min_version) = &args.min_version {
+        let minimum = version_numbers(min_version);
+        match version.as_deref().and_then(version_numbers) {
+            Some(actual) if minimum.as_ref().is_some_and(|minimum| older(&actual, minimum)) => {
+                return (WARNING, format!("{} version {} is older than {}", name, version.unwrap(), min_version));
+            }
+            Some(_) => {}
+            None => return (WARNING, format!("{} version unknown", name)),
+        }
+    }
+
+    match version {
+        Some(version) => (OK, format!("{} seen, version {}", name, version)),
+        None => (OK, format!("{} seen", name)),
+    }
+}
+
+pub fn check(global: &Global, args: &CheckArgs) -> io::Result<bool> {
+    if let Some(min_version) = &args.min_version {
+        if version_numbers(min_version).is_none() {
+            println!("MNDP UNKNOWN - invalid version '{}'", min_version);
+            process::exit(UNKNOWN);
+        }
+    }
+
+    let found = match find(global, args) {
+        Ok(found) => found,
+        Err(e) => {
+            println!("MNDP UNKNOWN - {}", e);
+            process::exit(UNKNOWN);
+        }
+    };
+    let (code, message) = status(args, found.as_ref().map(|(neighbor, _)| neighbor));
+    let label = match code {
+        OK => "OK",
+        WARNING => "WARNING",
+        _ => "CRITICAL",
+    };
+
+    let mut line = format!("MNDP {} - {}", label, message);
+    if let Some((discovered, reply_time)) = &found {
+        line.push_str(" |");
+        if let Some(uptime) = discovered.neighbor.uptime {
+            let _ = write!(line, " uptime={}s;;;0", uptime.as_secs());
+        }
+        let _ = write!(line, " reply_time={:.3}s;;;0", reply_time.as_secs_f64());
+    }
+    println!("{}", line);
+    process::exit(code);
+}
+
+#[test]
+fn test_version_numbers() {
+    assert_eq!(version_numbers("7.12.1 (stable)"), Some(vec![7, 12, 1]));
+    assert_eq!(version_numbers("7.15rc2 (testing)"), Some(vec![7, 15]));
+    assert_eq!(version_numbers("7"), Some(vec![7]));
+    assert_eq!(version_numbers("v7.12"), None);
+    assert_eq!(version_numbers(""), None);
+}
+
+#[test]
+fn test_older() {
+    assert!(!older(&[7, 12], &[7, 12, 0]));
+    assert!(!older(&[7, 12, 0], &[7, 12]));
+    assert!(!older(&[7, 12, 1], &[7, 12]));
+    assert!(older(&[7, 11, 2], &[7, 12]));
+    assert!(older(&[7, 12], &[7, 12, 1]));
+    assert!(!older(&[7, 12], &[7, 9]));
+}
+
+#[test]
+fn test_status() {
+    use std::time::SystemTime;
+
+    use mndp::Neighbor;
+
+    let mac = MacAddr6::new(0xc4, 0xad, 0x34, 0, 0, 1);
+    let args = |min_version: Option<&str>| CheckArgs {
+        expect_mac: mac,
+        min_version: min_version.map(String::from),
+        timeout: Duration::from_secs(5),
+    };
+    let found = |version: Option<&str>| {
+        let mut neighbor = Neighbor::builder().mac_address(mac).identity("router1").build();
+        neighbor.version = version.map(String::from);
+        DiscoveredNeighbor { neighbor, first_seen: SystemTime::now(), last_seen: SystemTime::now(), vlan: None, scope_id: None }
+    };
+
+    assert_eq!(status(&args(None), None), (CRITICAL, String::from("C4:AD:34:00:00:01 not seen")));
+    assert_eq!(status(&args(None), Some(&found(None))), (OK, String::from("router1 (C4:AD:34:00:00:01) seen")));
+    assert_eq!(status(&args(Some("7.12.0")), Some(&found(Some("7.12 (stable)")))).0, OK);
+    assert_eq!(
+        status(&args(Some("7.12")), Some(&found(Some("7.11.2 (stable)")))),
+        (WARNING, String::from("router1 (C4:AD:34:00:00:01) version 7.11.2 (stable) is older than 7.12")),
+    );
+    assert_eq!(status(&args(Some("7.12")), Some(&found(None))).0, WARNING);
+}
@@ -0,0 +1,148 @@
+//! `mndp decode` and `mndp encode`: convert between MNDP payloads and
+//! readable/JSON representations.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use base64::Engine;
+use bytes::Bytes;
+use clap::Args;
+use mndp::{MndpType, Neighbor, Packet, Transport, UdpTransport};
+
+use crate::output::Format;
+use crate::{invalid_data, Global};
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Hex or base64 payload (read from --file or stdin if not given)
+    payload: Option<String>,
+    /// Read the payload from a file ('-' for stdin)
+    #[arg(long, conflicts_with = "payload")]
+    file: Option<PathBuf>,
+    /// Treat the payload as base64 even if it is valid hex
+    #[arg(long)]
+    base64: bool,
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+    /// JSON neighbor description (read from --file or stdin if not given)
+    json: Option<String>,
+    /// Read the JSON from a file ('-' for stdin)
+    #[arg(long, conflicts_with = "json")]
+    file: Option<PathBuf>,
+    /// Write the payload as raw bytes instead of hex
+    #[arg(long)]
+    raw: bool,
+    /// Broadcast the payload instead of printing it
+    #[arg(long, conflicts_with = "raw")]
+    send: bool,
+}
+
+// Read the input text from the argument, a file or stdin
+fn read_input(arg: &Option<String>, file: &Option<PathBuf>) -> io::Result<String> {
+    match (arg, file) {
+        (Some(text), _) if text != "-" => Ok(text.clone()),
+        (_, Some(path)) if path.as_os_str() != "-" => fs::read_to_string(path),
+        _ => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            Ok(text)
+        }
+    }
+}
+
+// Decode hex (as copied from Wireshark, optionally with separators) or base64
+fn decode_payload(text: &str, force_base64: bool) -> io::Result<Vec<u8>> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if !force_base64 {
+        let digits = compact.trim_start_matches("0x").replace(':', "");
+        if let Ok(bytes) = hex::decode(&digits) {
+            return Ok(bytes);
+        }
+    }
+
+    base64::engine::general_purpose::STANDARD.decode(&compact)
+        .map_err(|_| invalid_data("payload is neither valid hex nor base64"))
+}
+
+// Print the packet header, each TLV and the decoded neighbor
+pub fn decode(global: &Global, args: &DecodeArgs) -> io::Result<bool> {
+    let bytes = decode_payload(&read_input(&args.payload, &args.file)?, args.base64)?;
+    let packet = Packet::from_bytes_with_limit(bytes, global.max_packet_size)
+        .map_err(|_| invalid_data("payload is shorter than an MNDP header or too long"))?;
+
+    let fields = packet.fields().iter().map(|tv| {
+        let name = MndpType::try_from(tv.typ).map(|typ| format!("{:?}", typ)).ok();
+        (tv, name)
+    });
+
+    match &global.format {
+        Format::Template(template) => println!("{}", template.neighbor(&packet.to_neighbor())),
+        Format::Table => {
+            println!("header: 0x{:04x}  sequence: {}", packet.header(), packet.sequence());
+            for (tv, name) in fields {
+                let value = match std::str::from_utf8(&tv.value) {
+                    Ok(s) if !s.is_empty() && s.chars().all(|c| !c.is_control()) => format!("{:?}", s),
+                    _ => hex::encode(&tv.value),
+                };
+                let name = name.unwrap_or_else(|| String::from("Unknown"));
+                println!("  {:>3} {:<14} len {:<4} {}", tv.typ, name, tv.value.len(), value);
+            }
+            println!();
+            print!("{}", packet.to_neighbor());
+        }
+        Format::Json => {
+            let fields: Vec<_> = fields.map(|(tv, name)| serde_json::json!({
+                "type": tv.typ,
+                "name": name,
+                "value": hex::encode(&tv.value),
+            })).collect();
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+                "header": packet.header(),
+                "sequence": packet.sequence(),
+                "fields": fields,
+                "neighbor": packet.to_neighbor(),
+            }))?);
+        }
+    }
+
+    Ok(true)
+}
+
+// Encode a JSON neighbor description, printing or broadcasting the payload
+pub fn encode(global: &Global, args: &EncodeArgs) -> io::Result<bool> {
+    let neighbor = Neighbor::from_json_str(&read_input(&args.json, &args.file)?)
+        .map_err(|e| invalid_data(format!("invalid neighbor JSON: {}", e)))?;
+    let bytes: Bytes = Packet::from_neighbor(&neighbor).to_bytes_with_limit(global.max_packet_size);
+
+    if args.send {
+        // Send on each selected address family that is available
+        let mut result = Err(io::Error::new(io::ErrorKind::NotConnected, "no transport available"));
+        let interface = global.interface()?;
+        let transports = global.in_netns(|| Ok(UdpTransport::open_all(global.port, global.family())
+            .into_iter()
+            .map(|transport| transport.and_then(|transport| match interface {
+                Some(interface) => transport.bind_interface(interface),
+                None => Ok(transport),
+            }))
+            .collect::<Vec<_>>()))?;
+        for transport in transports {
+            match transport.and_then(|transport| transport.broadcast(&bytes)) {
+                Ok(_) => result = Ok(()),
+                Err(e) if result.is_err() => result = Err(e),
+                Err(_) => {}
+            }
+        }
+        result?;
+    } else if args.raw {
+        io::stdout().write_all(&bytes)?;
+    } else {
+        println!("{}", hex::encode(&bytes));
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,294 @@
+//! `mndp daemon`: track neighbors continuously and publish them.
+//!
+//! The daemon runs in the foreground and is stopped with SIGINT or SIGTERM;
+//! it is Unix-only, with no Windows service mode.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use mndp::{Discovery, NeighborTable};
+
+use crate::output::print_event;
+use crate::{parse_secs, Global};
+
+// How often the state file is rewritten, so neighbors which don't change
+// keep their last-seen times current in it
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Longest wait for an event before checking for a signal
+const SIGNAL_POLL: Duration = Duration::from_millis(250);
+
+// Set by SIGINT or SIGTERM to stop the daemon after saving
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn stop(_signal: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+// Stop on SIGINT and SIGTERM rather than being killed, so the state file is
+// saved at exit
+fn handle_signals() -> io::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// State file to load neighbors from at start and save them to
+    #[arg(long)]
+    state: Option<PathBuf>,
+    /// Time after which a silent neighbor is considered lost, in seconds
+    #[arg(long, default_value = "180", value_parser = parse_secs)]
+    ttl: Duration,
+    /// Serve the HTTP API on this address
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "ADDR")]
+    http: Option<std::net::SocketAddr>,
+    /// POST discovery events to this URL (may be repeated)
+    #[cfg(feature = "webhook")]
+    #[arg(long, value_name = "URL")]
+    webhook: Vec<String>,
+    /// Publish discovery events to Kafka through these brokers
+    /// (comma-separated)
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "HOST:PORT", value_delimiter = ',')]
+    kafka: Vec<String>,
+    /// Kafka topic to publish discovery events to
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "TOPIC", default_value = "mndp")]
+    kafka_topic: String,
+    /// Connect to the Kafka brokers over TLS, trusting the system's
+    /// certificates
+    #[cfg(feature = "kafka")]
+    #[arg(long)]
+    kafka_tls: bool,
+    /// Authenticate to the Kafka brokers with this SASL mechanism
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_enum, value_name = "MECHANISM", requires = "kafka_username")]
+    kafka_sasl: Option<KafkaSasl>,
+    /// SASL username for --kafka-sasl
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "USERNAME", requires = "kafka_sasl")]
+    kafka_username: Option<String>,
+    /// SASL password for --kafka-sasl (default the KAFKA_PASSWORD
+    /// environment variable)
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "PASSWORD", requires = "kafka_sasl")]
+    kafka_password: Option<String>,
+    /// Compression of the record batches published to Kafka
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_enum, value_name = "CODEC", default_value = "none")]
+    kafka_compression: KafkaCompression,
+    /// Serve the gRPC API on this address
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR")]
+    grpc: Option<std::net::SocketAddr>,
+    /// Record discovery events in this SQLite database, for `mndp history`
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "PATH")]
+    history: Option<PathBuf>,
+    /// Publish neighbors on this D-Bus message bus
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    #[arg(long, value_enum)]
+    dbus: Option<DbusBus>,
+}
+
+#[cfg(feature = "kafka")]
+#[derive(Copy, Clone, clap::ValueEnum)]
+enum KafkaSasl {
+    Plain,
+    #[value(name = "scram-sha-256")]
+    ScramSha256,
+    #[value(name = "scram-sha-512")]
+    ScramSha512,
+}
+
+#[cfg(feature = "kafka")]
+#[derive(Copy, Clone, clap::ValueEnum)]
+enum KafkaCompression {
+    None,
+    Gzip,
+    Lz4,
+    Snappy,
+    Zstd,
+}
+
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+#[derive(Copy, Clone, clap::ValueEnum)]
+enum DbusBus {
+    Session,
+    System,
+}
+
+// Load the state file, if there is one yet
+// A state file which can't be read is reported and replaced, rather than
+// keeping the daemon from starting
+fn load_table(args: &DaemonArgs) -> NeighborTable {
+    let mut table = NeighborTable::with_ttl(args.ttl);
+    if let Some(path) = &args.state {
+        if path.exists() {
+            if let Err(e) = table.load_from_path(path) {
+                eprintln!("mndp: loading {}: {}; starting with an empty table", path.display(), e);
+                table = NeighborTable::with_ttl(args.ttl);
+            }
+        }
+    }
+    table
+}
+
+fn save_table(args: &DaemonArgs, discovery: &Discovery) {
+    if let Some(path) = &args.state {
+        if let Err(e) = discovery.table().save_to_path(path) {
+            eprintln!("mndp: saving {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+fn kafka_sink(args: &DaemonArgs) -> io::Result<mndp::KafkaSink> {
+    let mut builder = mndp::KafkaSink::builder(args.kafka.clone(), args.kafka_topic.clone())
+        .tls(args.kafka_tls)
+        .compression(match args.kafka_compression {
+            KafkaCompression::None => mndp::KafkaCompression::None,
+            KafkaCompression::Gzip => mndp::KafkaCompression::Gzip,
+            KafkaCompression::Lz4 => mndp::KafkaCompression::Lz4,
+            KafkaCompression::Snappy => mndp::KafkaCompression::Snappy,
+            KafkaCompression::Zstd => mndp::KafkaCompression::Zstd,
+        });
+    if let (Some(sasl), Some(username)) = (args.kafka_sasl, &args.kafka_username) {
+        let password = match &args.kafka_password {
+            Some(password) => password.clone(),
+            None => std::env::var("KAFKA_PASSWORD").map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "no Kafka password given with --kafka-password or KAFKA_PASSWORD")
+            })?,
+        };
+        let mechanism = match sasl {
+            KafkaSasl::Plain => mndp::KafkaSaslMechanism::Plain,
+            KafkaSasl::ScramSha256 => mndp::KafkaSaslMechanism::ScramSha256,
+            KafkaSasl::ScramSha512 => mndp::KafkaSaslMechanism::ScramSha512,
+        };
+        builder = builder.sasl(mechanism, username.clone(), password);
+    }
+    Ok(builder.build())
+}
+
+pub fn daemon(global: &Global, args: &DaemonArgs) -> io::Result<bool> {
+    let discovery = Arc::new(global.discovery()?.table(load_table(args)).start()?);
+    let events = discovery.subscribe();
+
+    // Services are stopped when they are dropped at exit
+    #[cfg(feature = "http")]
+    let _http = match args.http {
+        Some(addr) => Some(mndp::HttpServer::start(addr, discovery.clone())?),
+        None => None,
+    };
+    #[cfg(feature = "webhook")]
+    let webhooks: Vec<mndp::Webhook> = args.webhook.iter()
+        .map(|url| mndp::Webhook::builder(url.clone()).build())
+        .collect();
+    #[cfg(feature = "kafka")]
+    let kafka = match args.kafka.is_empty() {
+        true => None,
+        false => Some(kafka_sink(args)?),
+    };
+    #[cfg(feature = "grpc")]
+    {
+        if let Some(addr) = args.grpc {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            let discovery = discovery.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = runtime.block_on(mndp::GrpcService::serve(addr, discovery)) {
+                    eprintln!("mndp: gRPC server: {}", e);
+                }
+            });
+        }
+    }
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    let _dbus = match args.dbus {
+        Some(bus) => {
+            let bus = match bus {
+                DbusBus::Session => mndp::Bus::Session,
+                DbusBus::System => mndp::Bus::System,
+            };
+            Some(mndp::DbusService::start(bus, discovery.clone()).map_err(io::Error::other)?)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "sqlite")]
+    let history = match &args.history {
+        Some(path) => Some(mndp::History::open(path).map_err(io::Error::other)?),
+        None => None,
+    };
+    #[cfg(feature = "sqlite")]
+    let mut last_history_update = Instant::now();
+
+    handle_signals()?;
+    discovery.solicit()?;
+
+    // Save the first change promptly, then once per interval whether or not
+    // anything changed
+    let mut last_save: Option<Instant> = None;
+    let mut dirty = false;
+    while !STOP.load(Ordering::SeqCst) {
+        match events.recv_timeout(SIGNAL_POLL) {
+            Ok(event) => {
+                print_event(global, &event)?;
+                #[cfg(feature = "webhook")]
+                for webhook in &webhooks {
+                    webhook.notify(&event);
+                }
+                #[cfg(feature = "kafka")]
+                if let Some(kafka) = &kafka {
+                    kafka.notify(&event);
+                }
+                #[cfg(feature = "sqlite")]
+                if let Some(history) = &history {
+                    if let Err(e) = history.record(&event) {
+                        eprintln!("mndp: {}", e);
+                    }
+                }
+                dirty = true;
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let due = match last_save {
+            Some(t) => t.elapsed() >= SAVE_INTERVAL,
+            None => dirty,
+        };
+        if due {
+            save_table(args, &discovery);
+            last_save = Some(Instant::now());
+            dirty = false;
+        }
+
+        // Neighbors which don't change produce no events, so keep their
+        // last-seen times current
+        #[cfg(feature = "sqlite")]
+        if let Some(history) = &history {
+            if last_history_update.elapsed() >= SAVE_INTERVAL {
+                if let Err(e) = discovery.neighbors().iter().try_for_each(|neighbor| history.record_seen(neighbor)) {
+                    eprintln!("mndp: {}", e);
+                }
+                last_history_update = Instant::now();
+            }
+        }
+    }
+
+    // Stopping on a signal is a clean exit; discovery ending is not
+    save_table(args, &discovery);
+    Ok(STOP.load(Ordering::SeqCst))
+}
@@ -0,0 +1,257 @@
+//! `mndp discover` and `mndp export`: collect neighbors for a time window.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Args, Subcommand};
+use mndp::{interface_index, local_interfaces, topology_dot, DiscoveredNeighbor, NeighborTable, INFLUX_MEASUREMENT};
+
+use crate::announce::hostname;
+use crate::output::{print_neighbors, Format};
+use crate::{parse_secs, Global};
+
+// Listening time for `discover --once`
+const ONCE_WINDOW: Duration = Duration::from_secs(2);
+
+// How often `discover --watch` redraws the table, refreshing its ages
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Args)]
+pub struct DiscoverArgs {
+    /// Time to listen for replies, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs, conflicts_with = "once")]
+    timeout: Duration,
+    /// Solicit once, listen briefly and exit with status 1 if no neighbors
+    /// replied
+    #[arg(long)]
+    once: bool,
+    /// Keep listening until interrupted, redrawing the table every second
+    /// with the neighbors' current ages
+    #[arg(short, long, conflicts_with_all = ["timeout", "once"])]
+    watch: bool,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    target: ExportTarget,
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Write neighbors as a JSON array
+    Json(ExportFileArgs),
+    /// Write neighbors as a state file, as loaded by `mndp daemon --state`
+    State(ExportFileArgs),
+    /// Write a Graphviz graph of this host's interfaces and the neighbors
+    /// heard on each; the interface is known for neighbors heard over IPv6
+    /// or with -i
+    Dot(ExportFileArgs),
+    /// Write neighbors as InfluxDB line protocol; e.g. for Telegraf's exec
+    /// input
+    Influx(InfluxArgs),
+    /// Create or update a NetBox device for each neighbor
+    #[cfg(feature = "netbox")]
+    Netbox(NetboxArgs),
+}
+
+#[derive(Args)]
+struct ExportFileArgs {
+    /// Output file (default stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Time to listen for replies, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs)]
+    timeout: Duration,
+}
+
+#[derive(Args)]
+struct InfluxArgs {
+    /// Measurement name
+    #[arg(long, default_value = INFLUX_MEASUREMENT)]
+    measurement: String,
+    /// POST to this InfluxDB write URL instead of printing; e.g.
+    /// http://localhost:8086/api/v2/write?org=lab&bucket=mndp
+    #[cfg(feature = "influx")]
+    #[arg(long)]
+    url: Option<String>,
+    /// API token for --url (default the INFLUX_TOKEN environment variable)
+    #[cfg(feature = "influx")]
+    #[arg(long, requires = "url")]
+    token: Option<String>,
+    /// Time to listen for replies, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs)]
+    timeout: Duration,
+}
+
+#[cfg(feature = "netbox")]
+#[derive(Args)]
+struct NetboxArgs {
+    /// NetBox URL; e.g. https://netbox.example.com
+    #[arg(long)]
+    url: String,
+    /// API token (default the NETBOX_TOKEN environment variable)
+    #[arg(long)]
+    token: Option<String>,
+    /// ID of the site new devices are created in
+    #[arg(long)]
+    site: u64,
+    /// ID of the role new devices are created with
+    #[arg(long)]
+    role: u64,
+    /// ID of the device type used when none matches a neighbor's board
+    #[arg(long)]
+    device_type: Option<u64>,
+    /// Time to listen for replies, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs)]
+    timeout: Duration,
+}
+
+/// Solicit neighbors and collect replies for `timeout`.
+pub fn collect(global: &Global, timeout: Duration) -> io::Result<Vec<DiscoveredNeighbor>> {
+    let discovery = global.discovery()?.start()?;
+    discovery.solicit()?;
+    thread::sleep(timeout);
+    Ok(discovery.neighbors())
+}
+
+pub fn discover(global: &Global, args: &DiscoverArgs) -> io::Result<bool> {
+    if args.watch {
+        return watch(global);
+    }
+    let timeout = if args.once { ONCE_WINDOW } else { args.timeout };
+    let neighbors = collect(global, timeout)?;
+    print_neighbors(global, &neighbors)?;
+    Ok(!args.once || !neighbors.is_empty())
+}
+
+// Redraw the table in place, with neighbors added and expired as they
+// announce themselves or go quiet
+fn watch(global: &Global) -> io::Result<bool> {
+    if global.format != Format::Table || global.parsable {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "--watch needs the table format"));
+    }
+    let discovery = global.discovery()?.start()?;
+    discovery.solicit()?;
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        // Clear the terminal and move to its top left before each redraw
+        print!("\x1b[2J\x1b[H");
+        print_neighbors(global, &discovery.neighbors())?;
+    }
+}
+
+pub fn export(global: &Global, args: &ExportArgs) -> io::Result<bool> {
+    match &args.target {
+        ExportTarget::Json(file_args) => export_file(global, file_args, false),
+        ExportTarget::State(file_args) => export_file(global, file_args, true),
+        ExportTarget::Dot(file_args) => export_dot(global, file_args),
+        ExportTarget::Influx(influx_args) => export_influx(global, influx_args),
+        #[cfg(feature = "netbox")]
+        ExportTarget::Netbox(netbox_args) => export_netbox(global, netbox_args),
+    }
+}
+
+// Write neighbors as JSON or as a state file
+fn export_file(global: &Global, args: &ExportFileArgs, state: bool) -> io::Result<bool> {
+    let mut table = NeighborTable::new();
+    for neighbor in collect(global, args.timeout)? {
+        table.insert(neighbor);
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    if state {
+        table.save(&mut out)?;
+    } else {
+        writeln!(out, "{}", table.to_json_string()?)?;
+    }
+
+    out.flush()?;
+    Ok(true)
+}
+
+// Write the topology graph, with the interfaces which can be announced on
+// (or those selected with -i) even if no neighbors were heard on them
+fn export_dot(global: &Global, args: &ExportFileArgs) -> io::Result<bool> {
+    let mut neighbors = collect(global, args.timeout)?;
+    neighbors.sort_by_key(|n| n.neighbor.mac_address);
+
+    let names: Vec<String> = match global.interfaces.is_empty() {
+        true => global.in_netns(local_interfaces)?.into_iter().filter_map(|interface| interface.interface_name).collect(),
+        false => global.interfaces.clone(),
+    };
+    let interfaces: Vec<(u32, String)> = global.in_netns(|| {
+        Ok(names.into_iter().filter_map(|name| Some((interface_index(&name).ok()?, name))).collect())
+    })?;
+    let host = hostname().unwrap_or_else(|| String::from("localhost"));
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    out.write_all(topology_dot(&host, &interfaces, &neighbors).as_bytes())?;
+    out.flush()?;
+    Ok(true)
+}
+
+fn export_influx(global: &Global, args: &InfluxArgs) -> io::Result<bool> {
+    let mut neighbors = collect(global, args.timeout)?;
+    neighbors.sort_by_key(|n| n.neighbor.mac_address);
+
+    #[cfg(feature = "influx")]
+    if let Some(url) = &args.url {
+        let mut builder = mndp::InfluxWriter::builder(url.clone()).measurement(args.measurement.clone());
+        if let Some(token) = args.token.clone().or_else(|| std::env::var("INFLUX_TOKEN").ok()) {
+            builder = builder.token(token);
+        }
+        builder.build().write(&neighbors)?;
+        return Ok(true);
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for neighbor in &neighbors {
+        writeln!(out, "{}", neighbor.to_line_protocol(&args.measurement))?;
+    }
+    out.flush()?;
+    Ok(true)
+}
+
+// Sync each neighbor, reporting those that fail; succeeds if all synced
+#[cfg(feature = "netbox")]
+fn export_netbox(global: &Global, args: &NetboxArgs) -> io::Result<bool> {
+    let token = match &args.token {
+        Some(token) => token.clone(),
+        None => std::env::var("NETBOX_TOKEN")
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "no NetBox token given with --token or NETBOX_TOKEN"))?,
+    };
+    let mut builder = mndp::NetBox::builder(args.url.clone(), token).site(args.site).role(args.role);
+    if let Some(device_type) = args.device_type {
+        builder = builder.device_type(device_type);
+    }
+    let netbox = builder.build();
+
+    let mut neighbors = collect(global, args.timeout)?;
+    neighbors.sort_by_key(|n| n.neighbor.mac_address);
+
+    let mut ok = true;
+    for neighbor in &neighbors {
+        let name = mndp::sanitize(neighbor.neighbor.identity.as_deref().unwrap_or("(no identity)")).into_owned();
+        match netbox.sync(neighbor) {
+            Ok(id) if !global.quiet => println!("{}\t{}", name, id),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("mndp: {}: {}", name, e);
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
@@ -0,0 +1,94 @@
+//! `mndp history`: query the history recorded by `mndp daemon --history`.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use clap::Args;
+use mndp::macaddr::MacAddr6;
+use mndp::{format_rfc3339, sanitize, History};
+
+use crate::output::{print_neighbors, write_row, Format};
+use crate::Global;
+
+const SIGHTING_COLUMNS: [&str; 3] = ["TIME", "EVENT", "CHANGED"];
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// History database written by `mndp daemon --history`
+    #[arg(long, value_name = "PATH")]
+    db: PathBuf,
+    /// List when this device was seen, rather than all recorded neighbors
+    mac: Option<MacAddr6>,
+}
+
+pub fn history(global: &Global, args: &HistoryArgs) -> io::Result<bool> {
+    // Opening would otherwise create an empty database
+    if !args.db.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", args.db.display())));
+    }
+    let history = History::open(&args.db).map_err(io::Error::other)?;
+
+    let mac = match args.mac {
+        Some(mac) => mac,
+        None => {
+            print_neighbors(global, &history.neighbors().map_err(io::Error::other)?)?;
+            return Ok(true);
+        }
+    };
+    let neighbor = match history.neighbor(&mac).map_err(io::Error::other)? {
+        Some(neighbor) => neighbor,
+        None => {
+            eprintln!("mndp: {} has not been seen", mac);
+            return Ok(false);
+        }
+    };
+    let sightings = history.sightings(&mac).map_err(io::Error::other)?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if global.parsable {
+        if !global.quiet {
+            writeln!(out, "time\tevent\tchanged\tuptime")?;
+        }
+        for sighting in &sightings {
+            let time = sighting.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let uptime = sighting.uptime.map(|d| d.as_secs().to_string()).unwrap_or_default();
+            writeln!(out, "{}\t{}\t{}\t{}", time, sighting.event, sighting.changed.join(","), uptime)?;
+        }
+        return Ok(true);
+    }
+
+    match &global.format {
+        Format::Template(template) => writeln!(out, "{}", template.discovered(&neighbor))?,
+        Format::Json => {
+            let value = serde_json::json!({ "neighbor": neighbor, "sightings": sightings });
+            serde_json::to_writer_pretty(&mut out, &value)?;
+            writeln!(out)?;
+        }
+        Format::Table => {
+            if !global.quiet {
+                let name = neighbor.neighbor.identity.as_deref().map(sanitize).unwrap_or_default();
+                writeln!(out, "{} {} first seen {}, last seen {}\n", mac, name,
+                    format_rfc3339(neighbor.first_seen), format_rfc3339(neighbor.last_seen))?;
+            }
+            let rows: Vec<[String; 3]> = sightings.iter()
+                .map(|s| [format_rfc3339(s.time), s.event.clone(), s.changed.join(",")])
+                .collect();
+            let mut widths: Vec<usize> = SIGHTING_COLUMNS.iter().map(|c| c.len()).collect();
+            for cells in &rows {
+                for (width, cell) in widths.iter_mut().zip(cells.iter()) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            if !global.quiet {
+                write_row(&mut out, &widths, &SIGHTING_COLUMNS)?;
+            }
+            for cells in &rows {
+                write_row(&mut out, &widths, cells)?;
+            }
+        }
+    }
+    Ok(true)
+}
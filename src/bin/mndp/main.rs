@@ -0,0 +1,214 @@
+//! MNDP discovery tool.
+
+mod announce;
+mod check;
+mod codec;
+mod daemon;
+mod discover;
+#[cfg(feature = "sqlite")]
+mod history;
+mod output;
+mod ping;
+mod simulate;
+mod template;
+
+use std::io;
+use std::process;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use mndp::{AddressFamily, Discovery, DiscoveryBuilder, DEFAULT_MAX_PACKET_SIZE, MNDP_PORT};
+
+use crate::output::{Format, GroupBy};
+
+/// MikroTik Neighbor Discovery Protocol (MNDP) tool.
+#[derive(Parser)]
+#[command(name = "mndp", version)]
+struct Cli {
+    #[command(flatten)]
+    global: Global,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Options shared by all commands.
+#[derive(Args)]
+pub struct Global {
+    /// Network interface to use; discover, export, daemon, check, announce
+    /// and simulate accept it more than once (default all interfaces)
+    #[arg(short, long = "interface", global = true, value_name = "INTERFACE")]
+    pub interfaces: Vec<String>,
+    /// Use IPv4 only
+    #[arg(short = '4', global = true, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+    /// Use IPv6 only
+    #[arg(short = '6', global = true)]
+    pub ipv6: bool,
+    /// UDP port
+    #[arg(short, long, global = true, default_value_t = MNDP_PORT)]
+    pub port: u16,
+    /// Largest MNDP packet to send or accept, in bytes
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_PACKET_SIZE)]
+    pub max_packet_size: usize,
+    /// Ignore copies of an announcement (same MAC address and sequence
+    /// number) received within this many seconds of it
+    #[arg(long, global = true, value_name = "SECS", default_value = "0", value_parser = parse_secs)]
+    pub suppress_duplicates: Duration,
+    /// Report announcements which repeat or go back in their sequence
+    /// number on the same path, or which --capture shows came from other
+    /// addresses
+    #[arg(long, global = true)]
+    pub detect_spoofing: bool,
+    /// Output format: table, json, or a template printed for each neighbor
+    /// or event; e.g. '{identity}\t{mac}\t{ipv4}'
+    ///
+    /// Template placeholders are mac, identity, platform, version, board,
+    /// software_id, ipv4, ipv6, interface_name, uptime, uptime_secs, unpack,
+    /// the interface and vlan a neighbor was heard on, first_seen and
+    /// last_seen (RFC 3339) or first_seen_ago and last_seen_ago (e.g. '4m
+    /// ago'), and for events, event and changed (the changed fields, or why
+    /// it is suspicious). Use {{ and }} for literal
+    /// braces, and \t and \n for tabs and newlines.
+    #[arg(short, long, global = true, value_name = "FORMAT", default_value = "table", value_parser = Format::parse)]
+    pub format: Format,
+    /// Omit headers and informational messages
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// List neighbors under the interface or VLAN they were heard on
+    #[arg(long, global = true, value_enum, value_name = "FIELD", conflicts_with = "parsable")]
+    pub group_by: Option<GroupBy>,
+    /// Print tab-separated columns in a fixed order, for scripts
+    #[arg(long, global = true, conflicts_with = "format")]
+    pub parsable: bool,
+    /// Network namespace to open sockets in, by name or path
+    #[cfg(target_os = "linux")]
+    #[arg(long, global = true, value_name = "NAME")]
+    pub netns: Option<String>,
+    /// Capture frames on the interface, recording VLAN IDs (needs
+    /// CAP_NET_RAW)
+    #[cfg(all(feature = "raw", target_os = "linux"))]
+    #[arg(long, global = true, requires = "interfaces")]
+    pub capture: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Solicit neighbors and list those that reply
+    Discover(discover::DiscoverArgs),
+    /// Announce this host as a neighbor until interrupted
+    Announce(announce::AnnounceArgs),
+    /// Solicit a device and report the round-trip time of its reply
+    Ping(ping::PingArgs),
+    /// Decode a hex or base64 MNDP payload
+    Decode(codec::DecodeArgs),
+    /// Encode a JSON neighbor description as an MNDP payload
+    Encode(codec::EncodeArgs),
+    /// Discover neighbors and export them
+    Export(discover::ExportArgs),
+    /// Track neighbors continuously, reporting discovery events
+    ///
+    /// Runs in the foreground until SIGINT or SIGTERM, e.g. under systemd.
+    /// There is no Windows service mode: the socket and interface code is
+    /// Unix-only, so the daemon does not build for Windows.
+    Daemon(daemon::DaemonArgs),
+    /// Check that a device is visible, as a Nagios/Icinga plugin
+    Check(check::CheckArgs),
+    /// Announce fabricated neighbors until interrupted, for testing consumers
+    Simulate(simulate::SimulateArgs),
+    /// Query the history recorded by `mndp daemon --history`
+    #[cfg(feature = "sqlite")]
+    History(history::HistoryArgs),
+}
+
+impl Global {
+    /// Address family selected with `-4` or `-6`.
+    pub fn family(&self) -> AddressFamily {
+        match (self.ipv4, self.ipv6) {
+            (true, _) => AddressFamily::Ipv4,
+            (_, true) => AddressFamily::Ipv6,
+            _ => AddressFamily::Any,
+        }
+    }
+
+    /// Interface selected with `-i`, for commands which use at most one.
+    pub fn interface(&self) -> io::Result<Option<&str>> {
+        match self.interfaces.as_slice() {
+            [] => Ok(None),
+            [interface] => Ok(Some(interface)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "only one interface may be given")),
+        }
+    }
+
+    /// Run `f` in the namespace selected with `--netns`, if any; e.g. to
+    /// open sockets there.
+    pub fn in_netns<T: Send, F: FnOnce() -> io::Result<T> + Send>(&self, f: F) -> io::Result<T> {
+        #[cfg(target_os = "linux")]
+        if let Some(namespace) = &self.netns {
+            return mndp::in_namespace(namespace, f);
+        }
+        f()
+    }
+
+    /// Discovery configured from the shared options.
+    pub fn discovery(&self) -> io::Result<DiscoveryBuilder> {
+        let mut builder = Discovery::builder()
+            .port(self.port)
+            .address_family(self.family())
+            .max_packet_size(self.max_packet_size)
+            .suppress_duplicates(self.suppress_duplicates)
+            .detect_spoofing(self.detect_spoofing);
+        #[cfg(target_os = "linux")]
+        if let Some(namespace) = &self.netns {
+            builder = builder.namespace(namespace.clone());
+        }
+        for interface in &self.interfaces {
+            builder = builder.interface(interface.clone());
+            #[cfg(all(feature = "raw", target_os = "linux"))]
+            if self.capture {
+                builder = builder.transport(self.in_netns(|| mndp::RawTransport::open(interface, self.port))?);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// Parse a number of seconds (possibly fractional) as a `Duration`.
+pub fn parse_secs(value: &str) -> Result<Duration, String> {
+    value.parse::<f64>().ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("invalid number of seconds '{}'", value))
+}
+
+/// Error for malformed input data.
+pub fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Commands return whether they succeeded, for the exit status
+    let result = match &cli.command {
+        Command::Discover(args) => discover::discover(&cli.global, args),
+        Command::Announce(args) => announce::announce(&cli.global, args),
+        Command::Ping(args) => ping::ping(&cli.global, args),
+        Command::Decode(args) => codec::decode(&cli.global, args),
+        Command::Encode(args) => codec::encode(&cli.global, args),
+        Command::Export(args) => discover::export(&cli.global, args),
+        Command::Daemon(args) => daemon::daemon(&cli.global, args),
+        Command::Check(args) => check::check(&cli.global, args),
+        Command::Simulate(args) => simulate::simulate(&cli.global, args),
+        #[cfg(feature = "sqlite")]
+        Command::History(args) => history::history(&cli.global, args),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("mndp: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Rendering of neighbors and events in the selected output format.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+use mndp::{interface_name, sanitize, DiscoveredNeighbor, DiscoveryEvent, Neighbor};
+
+use crate::template::Template;
+use crate::Global;
+
+/// Output format selected with `--format`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Human-readable table
+    Table,
+    /// JSON (one object per line for streams of events)
+    Json,
+    /// One line per neighbor or event, from a template
+    Template(Template),
+}
+
+impl Format {
+    /// Parse a `--format` value: a format name, or otherwise a template.
+    pub fn parse(value: &str) -> Result<Format, String> {
+        match value {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            _ if value.contains('{') => Template::parse(value).map(Format::Template),
+            _ => Err(format!("unknown format '{}'; expected table, json or a template such as '{{identity}}'", value)),
+        }
+    }
+}
+
+/// Grouping of listed neighbors selected with `--group-by`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    /// Local interface each neighbor was heard on
+    Interface,
+    /// VLAN each neighbor was captured on (with --capture)
+    Vlan,
+}
+
+impl GroupBy {
+    // Group a neighbor is listed under, as an ID to sort by and a name; None
+    // for neighbors not known to be in one
+    fn group(self, discovered: &DiscoveredNeighbor) -> Option<(u32, String)> {
+        match self {
+            GroupBy::Interface => discovered.scope_id
+                .map(|index| (0, interface_name(index).unwrap_or_else(|_| index.to_string()))),
+            GroupBy::Vlan => discovered.vlan.map(|vlan| (u32::from(vlan), vlan.to_string())),
+        }
+    }
+
+    fn heading(self, group: &Option<(u32, String)>) -> String {
+        match (self, group) {
+            (GroupBy::Interface, Some((_, name))) => sanitize(name).into_owned(),
+            (GroupBy::Interface, None) => String::from("unknown interface"),
+            (GroupBy::Vlan, Some((_, id))) => format!("vlan {}", id),
+            (GroupBy::Vlan, None) => String::from("untagged"),
+        }
+    }
+
+    fn json(self, group: &Option<(u32, String)>, neighbors: &[DiscoveredNeighbor]) -> serde_json::Value {
+        match self {
+            GroupBy::Interface => serde_json::json!({
+                "interface": group.as_ref().map(|(_, name)| name),
+                "neighbors": neighbors,
+            }),
+            GroupBy::Vlan => serde_json::json!({
+                "vlan": group.as_ref().map(|(id, _)| id),
+                "neighbors": neighbors,
+            }),
+        }
+    }
+}
+
+// The VLAN column is only shown when a neighbor was captured with one
+const COLUMNS: [&str; 11] = [
+    "MAC ADDRESS", "IDENTITY", "PLATFORM", "VERSION", "BOARD", "IPV4 ADDRESS", "INTERFACE", "UPTIME",
+    "FIRST SEEN", "LAST SEEN", "VLAN",
+];
+
+// Cell text, with control characters from the network escaped
+fn text<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| sanitize(&v.to_string()).into_owned()).unwrap_or_default()
+}
+
+/// How long before `now` a time was, in its largest whole unit; e.g. '4m
+/// ago'. Times after `now` (from a clock stepped backwards) are '0s ago'.
+pub fn ago(time: SystemTime, now: SystemTime) -> String {
+    let secs = now.duration_since(time).unwrap_or_default().as_secs();
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+// Cells of a table row, with the first and last seen ages as of `now`
+fn row(discovered: &DiscoveredNeighbor, now: SystemTime) -> [String; 11] {
+    let neighbor = &discovered.neighbor;
+    [
+        text(&neighbor.mac_address),
+        text(&neighbor.identity),
+        text(&neighbor.platform),
+        text(&neighbor.version),
+        text(&neighbor.board),
+        text(&neighbor.ipv4_address),
+        text(&neighbor.interface_name),
+        text(&neighbor.uptime_human()),
+        ago(discovered.first_seen, now),
+        ago(discovered.last_seen, now),
+        text(&discovered.vlan),
+    ]
+}
+
+/// Columns of `--parsable` output, printed as a header unless `--quiet`.
+/// This order is stable: new fields are only ever appended.
+const PARSABLE_COLUMNS: [&str; 12] = [
+    "mac_address", "identity", "platform", "version", "board", "software_id",
+    "ipv4_address", "ipv6_address", "interface_name", "uptime", "unpack", "vlan",
+];
+
+/// Fixed `--parsable` columns of a neighbor, tab-separated. Tabs and
+/// newlines in values are escaped so they cannot break the row; uptime is in
+/// whole seconds.
+pub fn parsable_row(neighbor: &Neighbor, vlan: Option<u16>) -> String {
+    let cells = [
+        text(&neighbor.mac_address),
+        text(&neighbor.identity),
+        text(&neighbor.platform),
+        text(&neighbor.version),
+        text(&neighbor.board),
+        text(&neighbor.software_id),
+        text(&neighbor.ipv4_address),
+        text(&neighbor.ipv6_address),
+        text(&neighbor.interface_name),
+        text(&neighbor.uptime.map(|d| d.as_secs())),
+        text(&neighbor.unpack),
+        text(&vlan),
+    ];
+    cells.join("\t")
+}
+
+/// Write cells padded to column widths, as one line.
+pub fn write_row<W: Write, S: AsRef<str>>(out: &mut W, widths: &[usize], cells: &[S]) -> io::Result<()> {
+    let line: Vec<String> = cells.iter().zip(widths)
+        .map(|(cell, width)| format!("{:<width$}", cell.as_ref(), width = width))
+        .collect();
+    writeln!(out, "{}", line.join("  ").trim_end())
+}
+
+/// Print a list of neighbors, sorted by MAC address; with `--group-by`,
+/// under a heading for each group, sorted by name with neighbors not known
+/// to be in one last.
+pub fn print_neighbors(global: &Global, neighbors: &[DiscoveredNeighbor]) -> io::Result<()> {
+    let mut neighbors = neighbors.to_vec();
+    neighbors.sort_by_key(|n| n.neighbor.mac_address);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if global.parsable {
+        if !global.quiet {
+            writeln!(out, "{}", PARSABLE_COLUMNS.join("\t"))?;
+        }
+        for neighbor in &neighbors {
+            writeln!(out, "{}", parsable_row(&neighbor.neighbor, neighbor.vlan))?;
+        }
+        return Ok(());
+    }
+
+    let mut groups: BTreeMap<_, Vec<DiscoveredNeighbor>> = BTreeMap::new();
+    for neighbor in &neighbors {
+        let group = global.group_by.and_then(|group_by| group_by.group(neighbor));
+        groups.entry((group.is_none(), group)).or_default().push(neighbor.clone());
+    }
+    let heading = |group: &Option<(u32, String)>| global.group_by.map(|group_by| group_by.heading(group));
+
+    match &global.format {
+        Format::Json => {
+            match global.group_by {
+                Some(group_by) => {
+                    let groups: Vec<_> = groups.iter().map(|((_, group), neighbors)| group_by.json(group, neighbors)).collect();
+                    serde_json::to_writer_pretty(&mut out, &groups)?;
+                }
+                None => serde_json::to_writer_pretty(&mut out, &neighbors)?,
+            }
+            writeln!(out)
+        }
+        Format::Template(template) => {
+            for ((_, group), neighbors) in &groups {
+                if let Some(heading) = heading(group) {
+                    writeln!(out, "{}:", heading)?;
+                }
+                for neighbor in neighbors {
+                    writeln!(out, "{}", template.discovered(neighbor))?;
+                }
+            }
+            Ok(())
+        }
+        Format::Table => {
+            let columns = if neighbors.iter().any(|n| n.vlan.is_some()) { COLUMNS.len() } else { COLUMNS.len() - 1 };
+            let now = SystemTime::now();
+            let rows: Vec<[String; 11]> = groups.values().flatten().map(|neighbor| row(neighbor, now)).collect();
+            let mut widths: Vec<usize> = COLUMNS[..columns].iter().map(|c| c.len()).collect();
+            for cells in &rows {
+                for (width, cell) in widths.iter_mut().zip(cells.iter()) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+
+            if global.group_by.is_none() {
+                if !global.quiet {
+                    write_row(&mut out, &widths, &COLUMNS[..columns])?;
+                }
+                for cells in &rows {
+                    write_row(&mut out, &widths, cells)?;
+                }
+                return Ok(());
+            }
+
+            // Each group is indented under its heading, with the columns
+            // aligned across groups
+            let mut rows = rows.into_iter();
+            for (index, ((_, group), neighbors)) in groups.iter().enumerate() {
+                if index > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{}:", heading(group).unwrap_or_default())?;
+                if !global.quiet {
+                    write!(out, "  ")?;
+                    write_row(&mut out, &widths, &COLUMNS[..columns])?;
+                }
+                for cells in rows.by_ref().take(neighbors.len()) {
+                    write!(out, "  ")?;
+                    write_row(&mut out, &widths, &cells)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print a single discovery event as one line.
+pub fn print_event(global: &Global, event: &DiscoveryEvent) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if global.parsable {
+        let discovered = event.neighbor();
+        writeln!(out, "{}\t{}", event.name(), parsable_row(&discovered.neighbor, discovered.vlan))?;
+        return out.flush();
+    }
+
+    match &global.format {
+        Format::Json => {
+            serde_json::to_writer(&mut out, event)?;
+            writeln!(out)?;
+        }
+        Format::Template(template) => writeln!(out, "{}", template.event(event))?,
+        Format::Table => {
+            let mut cells = row(event.neighbor(), SystemTime::now()).to_vec();
+            if let DiscoveryEvent::Suspicious { reason, .. } = event {
+                cells.push(format!("({})", reason));
+            }
+            writeln!(out, "{:<8}  {}", event.name(), cells.iter()
+                .filter(|cell| !cell.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("  "))?;
+        }
+    }
+    out.flush()
+}
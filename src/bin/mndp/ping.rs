@@ -0,0 +1,133 @@
+//! `mndp ping`: solicit a single device and time its reply.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use clap::Args;
+use mndp::macaddr::MacAddr6;
+use mndp::{AddressFamily, Neighbor, Packet, Transport, UdpTransport, SOLICIT};
+
+use crate::output::{parsable_row, Format};
+use crate::{parse_secs, Global};
+
+#[derive(Args)]
+pub struct PingArgs {
+    /// MAC or IP address of the device
+    target: Target,
+    /// Time to wait for a reply, in seconds
+    #[arg(short, long, default_value = "5", value_parser = parse_secs)]
+    timeout: Duration,
+}
+
+/// Device to ping, by MAC address or IP address.
+#[derive(Clone)]
+enum Target {
+    Mac(MacAddr6),
+    Ip(IpAddr),
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Target, String> {
+        if let Ok(mac) = s.parse() {
+            Ok(Target::Mac(mac))
+        } else if let Ok(ip) = s.parse() {
+            Ok(Target::Ip(ip))
+        } else {
+            Err(format!("invalid MAC or IP address '{}'", s))
+        }
+    }
+}
+
+impl Target {
+    fn matches(&self, neighbor: &Neighbor, source: SocketAddr) -> bool {
+        match self {
+            Target::Mac(mac) => neighbor.mac_address == Some(*mac),
+            Target::Ip(IpAddr::V4(ip)) => source.ip() == *ip || neighbor.ipv4_address == Some(*ip),
+            Target::Ip(IpAddr::V6(ip)) => source.ip() == *ip || neighbor.ipv6_address == Some(*ip),
+        }
+    }
+}
+
+// Solicit the target and wait for its announcement, printing the reply
+pub fn ping(global: &Global, args: &PingArgs) -> io::Result<bool> {
+    // An IP target determines the family; a MAC target uses IPv4 unless -6
+    let ipv6 = match args.target {
+        Target::Ip(ip) => ip.is_ipv6(),
+        Target::Mac(_) => global.family() == AddressFamily::Ipv6,
+    };
+    let allowed = if ipv6 { global.family().ipv6() } else { global.family().ipv4() };
+    if !allowed {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "target address family is excluded by -4/-6"));
+    }
+    let interface = global.interface()?;
+    let transport = global.in_netns(|| {
+        let transport = match ipv6 {
+            true => UdpTransport::ipv6(global.port)?,
+            false => UdpTransport::ipv4(global.port)?,
+        };
+        match interface {
+            Some(interface) => transport.bind_interface(interface),
+            None => Ok(transport),
+        }
+    })?;
+
+    // Solicit an IP target directly; a MAC target can only be reached by broadcast
+    let solicit: Bytes = SOLICIT.to_bytes();
+    let start = Instant::now();
+    match args.target {
+        Target::Ip(ip) => transport.socket().send_to(&solicit, (ip, global.port)).map(|_| ())?,
+        Target::Mac(_) => transport.broadcast(&solicit)?,
+    }
+
+    let mut buf = vec![0u8; global.max_packet_size + 1];
+    loop {
+        let remaining = match args.timeout.checked_sub(start.elapsed()) {
+            Some(remaining) if remaining > Duration::from_millis(0) => remaining,
+            _ => break,
+        };
+        transport.socket().set_read_timeout(Some(remaining))?;
+
+        let (len, source) = match transport.socket().recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        };
+        let rtt = start.elapsed();
+
+        // Ignore our own solicitation, which is received when pinging ourselves
+        let bytes = Bytes::copy_from_slice(&buf[..len]);
+        let neighbor = match Packet::from_bytes_with_limit(bytes, global.max_packet_size) {
+            Ok(packet) if packet != SOLICIT => packet.to_neighbor(),
+            _ => continue,
+        };
+        if args.target.matches(&neighbor, source) {
+            let rtt_ms = rtt.as_secs_f64() * 1000.0;
+            match &global.format {
+                _ if global.parsable => {
+                    println!("{}\t{:.1}\t{}", source.ip(), rtt_ms, parsable_row(&neighbor, None));
+                }
+                Format::Table => {
+                    println!("reply from {}: time={:.1} ms", source.ip(), rtt_ms);
+                    print!("{}", neighbor);
+                }
+                Format::Template(template) => println!("{}", template.neighbor(&neighbor)),
+                Format::Json => println!("{}", serde_json::json!({
+                    "source": source.ip(),
+                    "rtt_ms": rtt_ms,
+                    "neighbor": neighbor,
+                })),
+            }
+            return Ok(true);
+        }
+    }
+
+    if !global.quiet {
+        eprintln!("no reply within {:.1} s", args.timeout.as_secs_f64());
+    }
+    Ok(false)
+}
@@ -0,0 +1,55 @@
+//! `mndp simulate`: announce a population of fabricated neighbors.
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use clap::Args;
+use mndp::Simulator;
+
+use crate::{parse_secs, Global};
+
+#[derive(Args)]
+pub struct SimulateArgs {
+    /// Number of neighbors
+    #[arg(short = 'n', long, default_value_t = 10)]
+    count: usize,
+    /// Seed the neighbors are fabricated from, to repeat a population
+    /// (default random)
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Interval between each neighbor's announcements, in seconds
+    #[arg(long, default_value = "60", value_parser = parse_secs)]
+    interval: Duration,
+    /// Vary each interval randomly by up to this many seconds either way
+    #[arg(long, default_value = "5", value_parser = parse_secs)]
+    jitter: Duration,
+}
+
+pub fn simulate(global: &Global, args: &SimulateArgs) -> io::Result<bool> {
+    let mut builder = Simulator::builder()
+        .count(args.count)
+        .port(global.port)
+        .address_family(global.family())
+        .max_packet_size(global.max_packet_size)
+        .interval(args.interval)
+        .jitter(args.jitter);
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    for interface in &global.interfaces {
+        builder = builder.interface(interface.clone());
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(namespace) = &global.netns {
+        builder = builder.namespace(namespace.clone());
+    }
+
+    let _simulator = builder.start()?;
+    if !global.quiet {
+        eprintln!("simulating {} neighbors until interrupted", args.count);
+    }
+    loop {
+        thread::park();
+    }
+}
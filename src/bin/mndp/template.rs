@@ -0,0 +1,235 @@
+//! Output templates given with `--format`, such as '{identity}\t{mac}':
+//! literal text with placeholders for fields, printed once per neighbor or
+//! event.
+
+use std::time::SystemTime;
+
+use mndp::{format_rfc3339, interface_name, sanitize, DiscoveredNeighbor, DiscoveryEvent, Neighbor};
+
+use crate::output::ago;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Field {
+    MacAddress,
+    Identity,
+    Platform,
+    Version,
+    Board,
+    SoftwareId,
+    Ipv4Address,
+    Ipv6Address,
+    InterfaceName,
+    Uptime,
+    UptimeSecs,
+    Unpack,
+    Vlan,
+    Interface,
+    FirstSeen,
+    LastSeen,
+    FirstSeenAgo,
+    LastSeenAgo,
+    Event,
+    Changed,
+}
+
+// Placeholder names, with short aliases for the common ones
+const FIELDS: [(&str, Field); 23] = [
+    ("mac", Field::MacAddress),
+    ("mac_address", Field::MacAddress),
+    ("identity", Field::Identity),
+    ("platform", Field::Platform),
+    ("version", Field::Version),
+    ("board", Field::Board),
+    ("software_id", Field::SoftwareId),
+    ("ipv4", Field::Ipv4Address),
+    ("ipv4_address", Field::Ipv4Address),
+    ("ipv6", Field::Ipv6Address),
+    ("ipv6_address", Field::Ipv6Address),
+    ("interface_name", Field::InterfaceName),
+    ("uptime", Field::Uptime),
+    ("uptime_secs", Field::UptimeSecs),
+    ("unpack", Field::Unpack),
+    ("vlan", Field::Vlan),
+    ("interface", Field::Interface),
+    ("first_seen", Field::FirstSeen),
+    ("last_seen", Field::LastSeen),
+    ("first_seen_ago", Field::FirstSeenAgo),
+    ("last_seen_ago", Field::LastSeenAgo),
+    ("event", Field::Event),
+    ("changed", Field::Changed),
+];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// Parsed output template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+// Value text, with control characters from the network escaped
+fn text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| sanitize(&v.to_string()).into_owned()).unwrap_or_default()
+}
+
+impl Template {
+    /// Parse a template. Placeholders are field names in braces; `{{` and
+    /// `}}` stand for literal braces, and `\t`, `\n` and `\\` for a tab,
+    /// newline and backslash, so they can be given without shell quoting.
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some('t')) => literal.push('\t'),
+                ('\\', Some('n')) => literal.push('\n'),
+                ('\\', Some('\\')) => literal.push('\\'),
+                ('{', Some('{')) => literal.push('{'),
+                ('}', Some('}')) => literal.push('}'),
+                ('{', _) => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder '{{{}'", name)),
+                        }
+                    }
+                    let field = FIELDS.iter().find(|(n, _)| *n == name).map(|&(_, field)| field).ok_or_else(|| {
+                        let names: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+                        format!("unknown placeholder '{{{}}}'; expected one of {}", name, names.join(", "))
+                    })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                    continue;
+                }
+                (c, _) => {
+                    literal.push(c);
+                    continue;
+                }
+            }
+            // Skip the second character of an escape
+            chars.next();
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Text(literal));
+        }
+        Ok(Template(segments))
+    }
+
+    /// Render the template for a neighbor outside the table; e.g. a decoded
+    /// packet. Discovery and event placeholders are empty.
+    pub fn neighbor(&self, neighbor: &Neighbor) -> String {
+        self.render(neighbor, None, None)
+    }
+
+    /// Render the template for a discovered neighbor.
+    pub fn discovered(&self, discovered: &DiscoveredNeighbor) -> String {
+        self.render(&discovered.neighbor, Some(discovered), None)
+    }
+
+    /// Render the template for a discovery event.
+    pub fn event(&self, event: &DiscoveryEvent) -> String {
+        self.render(&event.neighbor().neighbor, Some(event.neighbor()), Some(event))
+    }
+
+    fn render(&self, neighbor: &Neighbor, discovered: Option<&DiscoveredNeighbor>, event: Option<&DiscoveryEvent>) -> String {
+        let now = SystemTime::now();
+        let mut line = String::new();
+        for segment in &self.0 {
+            let field = match segment {
+                Segment::Text(text) => {
+                    line.push_str(text);
+                    continue;
+                }
+                Segment::Field(field) => field,
+            };
+            line.push_str(&match field {
+                Field::MacAddress => text(neighbor.mac_address),
+                Field::Identity => text(neighbor.identity.as_ref()),
+                Field::Platform => text(neighbor.platform.as_ref()),
+                Field::Version => text(neighbor.version.as_ref()),
+                Field::Board => text(neighbor.board.as_ref()),
+                Field::SoftwareId => text(neighbor.software_id.as_ref()),
+                Field::Ipv4Address => text(neighbor.ipv4_address),
+                Field::Ipv6Address => text(neighbor.ipv6_address),
+                Field::InterfaceName => text(neighbor.interface_name.as_ref()),
+                Field::Uptime => text(neighbor.uptime_human()),
+                Field::UptimeSecs => text(neighbor.uptime.map(|d| d.as_secs())),
+                Field::Unpack => text(neighbor.unpack),
+                Field::Vlan => text(discovered.and_then(|d| d.vlan)),
+                Field::Interface => text(discovered.and_then(|d| d.scope_id).and_then(|index| interface_name(index).ok())),
+                Field::FirstSeen => text(discovered.map(|d| format_rfc3339(d.first_seen))),
+                Field::LastSeen => text(discovered.map(|d| format_rfc3339(d.last_seen))),
+                Field::FirstSeenAgo => text(discovered.map(|d| ago(d.first_seen, now))),
+                Field::LastSeenAgo => text(discovered.map(|d| ago(d.last_seen, now))),
+                Field::Event => text(event.map(|e| e.name())),
+                Field::Changed => match event {
+                    Some(DiscoveryEvent::Suspicious { reason, .. }) => reason.to_string(),
+                    Some(event) => event.changed().iter().map(|field| field.name()).collect::<Vec<_>>().join(","),
+                    None => String::new(),
+                },
+            });
+        }
+        line
+    }
+}
+
+#[test]
+fn test_template_parse() {
+    let template = Template::parse(r"{identity}\t{{{mac}}}\n\\").unwrap();
+    assert_eq!(template, Template(vec![
+        Segment::Field(Field::Identity),
+        Segment::Text(String::from("\t{")),
+        Segment::Field(Field::MacAddress),
+        Segment::Text(String::from("}\n\\")),
+    ]));
+
+    // Other backslashes are literal
+    assert_eq!(Template::parse(r"\x").unwrap(), Template(vec![Segment::Text(String::from(r"\x"))]));
+
+    assert_eq!(Template::parse("{identity").unwrap_err(), "unclosed placeholder '{identity'");
+    let error = Template::parse("{name}").unwrap_err();
+    assert!(error.starts_with("unknown placeholder '{name}'; expected one of mac, mac_address,"), "{}", error);
+}
+
+#[test]
+fn test_template_render() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use mndp::{NeighborField, Suspicion};
+
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .identity("router\n1")
+        .uptime(Duration::from_secs(90))
+        .build();
+    let template = Template::parse("{event} {identity} {uptime_secs} {vlan} {first_seen} {changed}").unwrap();
+
+    // Control characters are escaped, and missing values are empty
+    assert_eq!(template.neighbor(&neighbor), r" router\n1 90   ");
+
+    let discovered = DiscoveredNeighbor {
+        neighbor,
+        first_seen: UNIX_EPOCH + Duration::from_secs(86400),
+        last_seen: UNIX_EPOCH + Duration::from_secs(86400),
+        vlan: Some(10),
+        scope_id: None,
+    };
+    assert_eq!(template.discovered(&discovered), r" router\n1 90 10 1970-01-02T00:00:00Z ");
+
+    let event = DiscoveryEvent::Updated {
+        neighbor: discovered.clone(),
+        changed: vec![NeighborField::Identity, NeighborField::Uptime],
+    };
+    assert_eq!(template.event(&event), r"updated router\n1 90 10 1970-01-02T00:00:00Z identity,uptime");
+    let event = DiscoveryEvent::Suspicious { neighbor: discovered, reason: Suspicion::RepeatedSequence };
+    assert!(template.event(&event).starts_with("suspicious "));
+}
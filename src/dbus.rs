@@ -0,0 +1,186 @@
+//! D-Bus interface to a `Discovery` service.
+//!
+//! The service is published as `org.mndp.Discovery` at `/org/mndp/Discovery`
+//! with a `Neighbors` property and `NeighborAdded`/`NeighborRemoved` signals.
+//! Each neighbor is a string dictionary keyed by field name; e.g.
+//! `identity`, `mac_address`, `uptime` (seconds) and `last_seen` (seconds
+//! since the Unix epoch).
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use zbus::blocking::connection;
+use zbus::object_server::SignalEmitter;
+
+use crate::timestamp::unix_secs;
+use crate::{DiscoveredNeighbor, Discovery, DiscoveryEvent};
+
+/// Well-known bus name and interface name.
+pub const DBUS_NAME: &str = "org.mndp.Discovery";
+
+/// Object path of the discovery object.
+pub const DBUS_PATH: &str = "/org/mndp/Discovery";
+
+// How often signal forwarding checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Message bus to publish the service on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bus {
+    /// Per-user session bus.
+    Session,
+    /// System-wide bus (usually requires a bus policy allowing the name).
+    System,
+}
+
+/// Running D-Bus service. The service is removed from the bus when it is
+/// dropped.
+pub struct DbusService {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct DiscoveryInterface {
+    discovery: Arc<Discovery>,
+}
+
+#[zbus::interface(name = "org.mndp.Discovery")]
+impl DiscoveryInterface {
+    /// All currently known neighbors.
+    #[zbus(property)]
+    fn neighbors(&self) -> Vec<HashMap<String, String>> {
+        let mut neighbors = self.discovery.neighbors();
+        neighbors.sort_by_key(|n| n.neighbor.mac_address);
+        neighbors.iter().map(to_dict).collect()
+    }
+
+    /// Send a solicitation, prompting neighbors to announce themselves.
+    fn solicit(&self) -> zbus::fdo::Result<()> {
+        self.discovery.solicit().map_err(|e| zbus::fdo::Error::IOError(e.to_string()))
+    }
+
+    /// Emitted when a neighbor is heard from for the first time.
+    #[zbus(signal)]
+    async fn neighbor_added(emitter: &SignalEmitter<'_>, neighbor: HashMap<String, String>) -> zbus::Result<()>;
+
+    /// Emitted when a neighbor expires, with its MAC address.
+    #[zbus(signal)]
+    async fn neighbor_removed(emitter: &SignalEmitter<'_>, mac_address: String) -> zbus::Result<()>;
+}
+
+impl DbusService {
+    /// Publish `discovery` on `bus` and start forwarding its events as signals.
+    pub fn start(bus: Bus, discovery: Arc<Discovery>) -> zbus::Result<DbusService> {
+        let events = discovery.subscribe();
+        let builder = match bus {
+            Bus::Session => connection::Builder::session()?,
+            Bus::System => connection::Builder::system()?,
+        };
+        let connection = builder
+            .name(DBUS_NAME)?
+            .serve_at(DBUS_PATH, DiscoveryInterface { discovery })?
+            .build()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = thread::spawn(move || {
+            let iface = match connection.object_server().interface::<_, DiscoveryInterface>(DBUS_PATH) {
+                Ok(iface) => iface,
+                Err(_) => return,
+            };
+            let emitter = iface.signal_emitter();
+
+            while thread_running.load(Ordering::Relaxed) {
+                let event = match events.recv_timeout(POLL_INTERVAL) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+
+                // Signal delivery is best-effort
+                let _ = zbus::block_on(async {
+                    match &event {
+                        DiscoveryEvent::Added(n) => DiscoveryInterface::neighbor_added(emitter, to_dict(n)).await?,
+                        DiscoveryEvent::Expired(n) => {
+                            let mac = n.neighbor.mac_address.map(|m| m.to_string()).unwrap_or_default();
+                            DiscoveryInterface::neighbor_removed(emitter, mac).await?
+                        }
+                        _ => {}
+                    }
+                    iface.get().neighbors_changed(emitter).await
+                });
+            }
+        });
+
+        Ok(DbusService {
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DbusService {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn to_dict(discovered: &DiscoveredNeighbor) -> HashMap<String, String> {
+    let neighbor = &discovered.neighbor;
+    let mut dict = HashMap::new();
+    let mut insert = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            dict.insert(String::from(key), value);
+        }
+    };
+
+    insert("board", neighbor.board.clone());
+    insert("identity", neighbor.identity.clone());
+    insert("interface_name", neighbor.interface_name.clone());
+    insert("ipv4_address", neighbor.ipv4_address.map(|a| a.to_string()));
+    insert("ipv6_address", neighbor.ipv6_address.map(|a| a.to_string()));
+    insert("mac_address", neighbor.mac_address.map(|a| a.to_string()));
+    insert("platform", neighbor.platform.clone());
+    insert("software_id", neighbor.software_id.clone());
+    insert("unpack", neighbor.unpack.map(|u| u.to_string()));
+    insert("uptime", neighbor.uptime.map(|d| d.as_secs().to_string()));
+    insert("version", neighbor.version.clone());
+    insert("vlan", discovered.vlan.map(|v| v.to_string()));
+    insert("scope_id", discovered.scope_id.map(|s| s.to_string()));
+    for (key, time) in &[("first_seen", discovered.first_seen), ("last_seen", discovered.last_seen)] {
+        insert(key, Some(unix_secs(*time).to_string()));
+    }
+
+    dict
+}
+
+#[test]
+fn test_dbus_neighbor_dict() {
+    use std::time::UNIX_EPOCH;
+
+    use crate::Neighbor;
+
+    let dict = to_dict(&DiscoveredNeighbor {
+        neighbor: Neighbor::builder()
+            .mac_address([0, 0, 0, 0, 0, 1])
+            .identity("router1")
+            .uptime(Duration::from_secs(60))
+            .build(),
+        first_seen: UNIX_EPOCH + Duration::from_secs(10),
+        last_seen: UNIX_EPOCH + Duration::from_secs(20),
+        vlan: None,
+        scope_id: None,
+    });
+    assert_eq!(dict.len(), 5);
+    assert_eq!(dict["mac_address"], "00:00:00:00:00:01");
+    assert_eq!(dict["identity"], "router1");
+    assert_eq!(dict["uptime"], "60");
+    assert_eq!(dict["last_seen"], "20");
+}
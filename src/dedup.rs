@@ -0,0 +1,83 @@
+//! Suppression of duplicate announcements, which devices send on several
+//! paths at once (IPv4 and IPv6, or several interfaces or VLANs).
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use macaddr::MacAddr6;
+
+// Announcements seen within the window, by MAC address and sequence number.
+// Keeping every sequence number rather than the latest also catches a copy
+// delayed behind the device's next announcement, which would otherwise look
+// like a reboot if its uptime is lower.
+#[derive(Debug, Default)]
+pub(crate) struct DuplicateFilter {
+    window: Duration,
+    seen: HashMap<(MacAddr6, u16), SystemTime>,
+}
+
+impl DuplicateFilter {
+    // A zero window disables suppression
+    pub(crate) fn new(window: Duration) -> DuplicateFilter {
+        DuplicateFilter { window, seen: HashMap::new() }
+    }
+
+    // Whether the announcement was already seen within the window; if not,
+    // it is remembered
+    pub(crate) fn is_duplicate(&mut self, mac: MacAddr6, sequence: u16, now: SystemTime) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+        match self.seen.get(&(mac, sequence)) {
+            // A clock stepped backwards counts as within the window
+            Some(seen) if now.duration_since(*seen).unwrap_or_default() < self.window => true,
+            _ => {
+                self.seen.insert((mac, sequence), now);
+                false
+            }
+        }
+    }
+
+    // Forget announcements older than the window
+    pub(crate) fn prune(&mut self, now: SystemTime) {
+        let window = self.window;
+        self.seen.retain(|_, seen| now.duration_since(*seen).unwrap_or_default() < window);
+    }
+}
+
+#[test]
+fn test_duplicate_filter() {
+    use crate::{Discovery, DiscoveryEvent, MockClock, MockTransport, Neighbor, Packet};
+
+    let mac = MacAddr6::new(0, 0, 0, 0, 0, 1);
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let mut filter = DuplicateFilter::new(Duration::from_secs(5));
+    assert!(!filter.is_duplicate(mac, 1, start));
+    assert!(filter.is_duplicate(mac, 1, start + Duration::from_secs(4)));
+    assert!(!filter.is_duplicate(mac, 2, start + Duration::from_secs(4)));
+    filter.prune(start + Duration::from_secs(6));
+    assert_eq!(filter.seen.len(), 1);
+    assert!(!filter.is_duplicate(mac, 1, start + Duration::from_secs(6)));
+    assert!(!DuplicateFilter::new(Duration::ZERO).is_duplicate(mac, 1, start));
+
+    // A copy of the first announcement arriving after the second does not
+    // look like a reboot
+    let transport = MockTransport::new();
+    let discovery = Discovery::builder()
+        .transport(transport.clone())
+        .clock(MockClock::new(start))
+        .suppress_duplicates(Duration::from_secs(5))
+        .start()
+        .unwrap();
+    let events = discovery.subscribe();
+    let source = "192.0.2.1:5678".parse().unwrap();
+    for (sequence, uptime) in [(1, 100), (2, 160), (1, 100)] {
+        let neighbor = Neighbor::builder().mac_address(mac).uptime(Duration::from_secs(uptime)).build();
+        let mut packet = Packet::from_neighbor(&neighbor);
+        packet.set_sequence(sequence);
+        transport.inject(packet.to_bytes::<bytes::Bytes>(), source);
+    }
+    assert!(matches!(events.recv_timeout(Duration::from_secs(5)).unwrap(), DiscoveryEvent::Added(_)));
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_err());
+    assert_eq!(discovery.neighbor(&mac).unwrap().neighbor.uptime, Some(Duration::from_secs(160)));
+}
@@ -0,0 +1,897 @@
+//! Discovery service which listens for MNDP announcements and maintains a
+//! `NeighborTable`.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use macaddr::MacAddr6;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::addressing::{interface_index, ipv4_destination, ipv6_destination};
+use crate::dedup::DuplicateFilter;
+#[cfg(target_os = "linux")]
+use crate::mmsg::RECV_BATCH;
+use crate::spoof::SequenceTracker;
+use crate::{DiscoveredNeighbor, DiscoveryEvent, Neighbor, NeighborTable, Packet, Received, DEFAULT_MAX_PACKET_SIZE, SOLICIT};
+
+/// UDP port used by MNDP.
+pub const MNDP_PORT: u16 = 5678;
+
+/// IPv6 multicast group used by MNDP (all nodes, link-local scope).
+pub const MNDP_IPV6_GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+// How often blocked receive calls return to check for shutdown, and how
+// often the table is checked for expired neighbors
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Size of the chunks datagrams are received into
+const RECV_CHUNK_SIZE: usize = 64 * 1024;
+
+// Most datagrams received at once
+#[cfg(not(target_os = "linux"))]
+const RECV_BATCH: usize = 1;
+
+/// Source of the current time for the discovery service.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock reading the system time.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Datagram transport used by the discovery service to exchange MNDP packets.
+///
+/// `recv_from` should return within a short time (e.g. via a read timeout)
+/// with an error of kind `WouldBlock` or `TimedOut` when nothing was
+/// received, so the service can shut down promptly.
+pub trait Transport: Send + Sync {
+    /// Receive a single datagram into `buf`, returning its length and source.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    /// Receive a single datagram into `buf`, returning details of how it was
+    /// received. Transports which see more than the source address (e.g.
+    /// the VLAN of a captured frame) override this; by default it calls
+    /// `recv_from`.
+    fn recv(&self, buf: &mut [u8]) -> io::Result<Received> {
+        let (len, source) = self.recv_from(buf)?;
+        Ok(Received { len, source, vlan: None, scope_id: source_scope_id(&source), link_source: None })
+    }
+
+    /// Receive one or more datagrams into consecutive `size`-byte slots of
+    /// `buf`, appending details of each to `received` in order. Waits for
+    /// the first datagram as `recv` does, but not for any others. Transports
+    /// which can take several datagrams at once override this; by default it
+    /// receives one with `recv`.
+    fn recv_batch(&self, buf: &mut [u8], size: usize, received: &mut Vec<Received>) -> io::Result<()> {
+        received.push(self.recv(&mut buf[..size])?);
+        Ok(())
+    }
+
+    /// Send a datagram to all neighbors reachable via this transport.
+    fn broadcast(&self, buf: &[u8]) -> io::Result<()>;
+}
+
+// After a receive error, wait before trying again unless it was a timeout,
+// so a transport which keeps failing (e.g. its interface went away) doesn't
+// spin its receive thread
+pub(crate) fn recv_backoff(e: &io::Error) {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted => {}
+        _ => thread::sleep(POLL_INTERVAL),
+    }
+}
+
+// Interface index of an IPv6 source address with a zone
+pub(crate) fn source_scope_id(source: &SocketAddr) -> Option<u32> {
+    match source {
+        SocketAddr::V6(addr) if addr.scope_id() != 0 => Some(addr.scope_id()),
+        _ => None,
+    }
+}
+
+/// Address families used by the default UDP transports.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AddressFamily {
+    /// Both IPv4 and IPv6, tolerating failure of one.
+    #[default]
+    Any,
+    /// IPv4 broadcast only.
+    Ipv4,
+    /// IPv6 multicast only.
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Whether IPv4 is used.
+    pub fn ipv4(self) -> bool {
+        self != AddressFamily::Ipv6
+    }
+
+    /// Whether IPv6 is used.
+    pub fn ipv6(self) -> bool {
+        self != AddressFamily::Ipv4
+    }
+}
+
+/// UDP socket transport sending to a broadcast or multicast destination.
+#[derive(Debug)]
+pub struct UdpTransport {
+    pub(crate) socket: UdpSocket,
+    pub(crate) destination: SocketAddr,
+    pub(crate) index: Option<u32>,
+}
+
+impl UdpTransport {
+    /// Create a transport from an already bound socket which sends to
+    /// `destination`.
+    pub fn new(socket: UdpSocket, destination: SocketAddr) -> UdpTransport {
+        UdpTransport { socket, destination, index: None }
+    }
+
+    /// Bind an IPv4 socket on `port` which broadcasts to 255.255.255.255.
+    pub fn ipv4(port: u16) -> io::Result<UdpTransport> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        set_reuse(&socket)?;
+        socket.set_broadcast(true)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+
+        Ok(UdpTransport::new(socket.into(), ipv4_destination(port).into()))
+    }
+
+    /// Bind an IPv6 socket on `port` which sends to the MNDP multicast group.
+    pub fn ipv6(port: u16) -> io::Result<UdpTransport> {
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(true)?;
+        set_reuse(&socket)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        socket.bind(&SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0).into())?;
+
+        Ok(UdpTransport::new(socket.into(), ipv6_destination(port, 0).into()))
+    }
+
+    /// Create a transport from a socket opened elsewhere; e.g. inherited from
+    /// a process in another network namespace. It sends to the default
+    /// destination of its address family on the port it is bound to.
+    pub fn from_socket(socket: UdpSocket) -> io::Result<UdpTransport> {
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let destination = match socket.local_addr()? {
+            SocketAddr::V4(addr) => {
+                socket.set_broadcast(true)?;
+                ipv4_destination(addr.port()).into()
+            }
+            SocketAddr::V6(addr) => ipv6_destination(addr.port(), 0).into(),
+        };
+        Ok(UdpTransport::new(socket, destination))
+    }
+
+    /// Open the transports of the given address families on `port`,
+    /// returning the result for each (IPv4 first).
+    pub fn open_all(port: u16, family: AddressFamily) -> Vec<io::Result<UdpTransport>> {
+        let mut transports = Vec::new();
+        if family.ipv4() {
+            transports.push(UdpTransport::ipv4(port));
+        }
+        if family.ipv6() {
+            transports.push(UdpTransport::ipv6(port));
+        }
+        transports
+    }
+
+    /// Restrict the transport to a single network interface; e.g. 'ether1'.
+    /// IPv6 multicast is also sent via this interface. This uses
+    /// `SO_BINDTODEVICE` on Linux and `IP_BOUND_IF`/`IPV6_BOUND_IF` on macOS
+    /// and Solaris, and is unsupported elsewhere.
+    pub fn bind_interface(mut self, interface: &str) -> io::Result<UdpTransport> {
+        let index = interface_index(interface)?;
+        bind_device(&self.socket, interface, index)?;
+
+        if let SocketAddr::V6(destination) = &mut self.destination {
+            destination.set_scope_id(index);
+        }
+        self.index = Some(index);
+        Ok(self)
+    }
+
+    /// The underlying socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+// Allow other MNDP tools on the host to bind the port too. BSD-derived
+// stacks only share a wildcard-bound UDP port between sockets which all set
+// SO_REUSEPORT, where Linux and Solaris need only SO_REUSEADDR.
+fn set_reuse(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_address(true)?;
+    #[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+        target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+    socket.set_reuse_port(true)?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &UdpSocket, interface: &str, _: u32) -> io::Result<()> {
+    socket2::SockRef::from(socket).bind_device(Some(interface.as_bytes()))
+}
+
+// IP_BOUND_IF and IPV6_BOUND_IF take the interface index, and like
+// SO_BINDTODEVICE restrict sending as well as receiving
+#[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+    target_os = "illumos", target_os = "solaris"))]
+fn bind_device(socket: &UdpSocket, _: &str, index: u32) -> io::Result<()> {
+    let index = std::num::NonZeroU32::new(index);
+    let socket = socket2::SockRef::from(socket);
+    match socket.local_addr()?.is_ipv6() {
+        true => socket.bind_device_by_index_v6(index),
+        false => socket.bind_device_by_index_v4(index),
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux",
+    target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+    target_os = "illumos", target_os = "solaris")))]
+fn bind_device(_: &UdpSocket, _: &str, _: u32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "binding to an interface is not supported on this platform"))
+}
+
+// BSD-derived stacks send the limited broadcast only through the interface
+// of the default route, so an unbound IPv4 socket sends to the subnet
+// broadcast address of each interface instead, falling back to the limited
+// broadcast if none are found
+#[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+    target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+pub(crate) fn broadcast_subnets<F: Fn(SocketAddr) -> io::Result<usize>>(send_to: F, port: u16) -> io::Result<()> {
+    let mut destinations = Vec::new();
+    for interface in crate::local_interfaces()? {
+        if let Some(name) = &interface.interface_name {
+            destinations.extend(crate::interface_broadcasts(name, port)?);
+        }
+    }
+    if destinations.is_empty() {
+        destinations.push(ipv4_destination(port));
+    }
+
+    let mut result = Ok(());
+    let mut sent = false;
+    for destination in destinations {
+        match send_to(destination.into()) {
+            Ok(_) => sent = true,
+            Err(e) => result = Err(e),
+        }
+    }
+    if sent { Ok(()) } else { result }
+}
+
+impl Transport for UdpTransport {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    // A transport bound to an interface receives only on that interface
+    fn recv(&self, buf: &mut [u8]) -> io::Result<Received> {
+        let (len, source) = self.recv_from(buf)?;
+        let scope_id = self.index.or_else(|| source_scope_id(&source));
+        Ok(Received { len, source, vlan: None, scope_id, link_source: None })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn recv_batch(&self, buf: &mut [u8], size: usize, received: &mut Vec<Received>) -> io::Result<()> {
+        let start = received.len();
+        crate::mmsg::recv_batch(self.socket.as_raw_fd(), buf, size, received)?;
+        for received in &mut received[start..] {
+            received.scope_id = self.index.or_else(|| source_scope_id(&received.source));
+        }
+        Ok(())
+    }
+
+    fn broadcast(&self, buf: &[u8]) -> io::Result<()> {
+        #[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+            target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+        match self.destination {
+            SocketAddr::V4(destination) if self.index.is_none() && *destination.ip() == Ipv4Addr::BROADCAST => {
+                return broadcast_subnets(|destination| self.socket.send_to(buf, destination), destination.port());
+            }
+            _ => {}
+        }
+        self.socket.send_to(buf, self.destination).map(|_| ())
+    }
+}
+
+// Broadcast on every transport, succeeding if at least one send succeeded
+pub(crate) fn broadcast_all<'a, I: IntoIterator<Item = &'a dyn Transport>>(transports: I, buf: &[u8]) -> io::Result<()> {
+    let mut result = Ok(());
+    let mut sent = false;
+    for transport in transports {
+        match transport.broadcast(buf) {
+            Ok(_) => sent = true,
+            Err(e) => result = Err(e),
+        }
+    }
+    if sent { Ok(()) } else { result }
+}
+
+// Open the default transports of the given families, optionally bound to an
+// interface and in a network namespace, tolerating failure of one
+pub(crate) fn default_transports(port: u16, family: AddressFamily, interface: Option<&str>, namespace: Option<&str>) -> io::Result<Vec<Box<dyn Transport>>> {
+    let transports = default_udp_transports(port, family, interface, namespace)?;
+    Ok(transports.into_iter().map(|transport| Box::new(transport) as Box<dyn Transport>).collect())
+}
+
+pub(crate) fn default_udp_transports(port: u16, family: AddressFamily, interface: Option<&str>, namespace: Option<&str>) -> io::Result<Vec<UdpTransport>> {
+    #[cfg(target_os = "linux")]
+    if let Some(namespace) = namespace {
+        return crate::netns::in_namespace(namespace, || default_udp_transports(port, family, interface, None));
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = namespace;
+
+    let mut transports = Vec::new();
+    let mut error = None;
+    for transport in UdpTransport::open_all(port, family) {
+        let transport = transport.and_then(|transport| match interface {
+            Some(interface) => transport.bind_interface(interface),
+            None => Ok(transport),
+        });
+        match transport {
+            Ok(transport) => transports.push(transport),
+            Err(e) => error = Some(e),
+        }
+    }
+    match (transports.is_empty(), error) {
+        (true, Some(e)) => Err(e),
+        _ => Ok(transports),
+    }
+}
+
+// Neighbor described by a datagram and its sequence number, unless it is
+// malformed, too large or a solicitation (which carries no neighbor
+// information)
+pub(crate) fn decode(bytes: Bytes, max_packet_size: usize) -> Option<(Neighbor, u16)> {
+    match Packet::from_bytes_with_limit(bytes, max_packet_size) {
+        Ok(packet) if packet != SOLICIT => Some((packet.to_neighbor(), packet.sequence())),
+        _ => None,
+    }
+}
+
+// Receive buffer which hands out each datagram as `Bytes` sharing a larger
+// chunk, rather than allocating per datagram. Once every datagram of a chunk
+// is dropped the chunk is reclaimed, so a listener which parses and discards
+// packets settles on a single allocation.
+pub(crate) struct RecvPool {
+    chunk: BytesMut,
+    max_packet_size: usize,
+    received: Vec<Received>,
+}
+
+impl RecvPool {
+    pub(crate) fn new(max_packet_size: usize) -> RecvPool {
+        RecvPool {
+            chunk: BytesMut::with_capacity(RECV_CHUNK_SIZE.max(max_packet_size + 1)),
+            max_packet_size,
+            received: Vec::new(),
+        }
+    }
+
+    // Receive a batch of datagrams (see `Transport::recv_batch`), appending
+    // them to `datagrams`
+    pub(crate) fn recv_batch(&mut self, transport: &dyn Transport, datagrams: &mut Vec<(Bytes, Received)>) -> io::Result<()> {
+        // One byte extra detects (and drops) oversized datagrams, which are
+        // truncated to the slot size. Reserving reuses the rest of the chunk,
+        // or the whole chunk if it is no longer shared, and only otherwise
+        // allocates a new one.
+        let size = self.max_packet_size + 1;
+        let slots = (RECV_CHUNK_SIZE / size).clamp(1, RECV_BATCH);
+        self.chunk.reserve(slots * size);
+        self.chunk.resize(slots * size, 0);
+
+        self.received.clear();
+        let result = transport.recv_batch(&mut self.chunk, size, &mut self.received);
+        for received in self.received.drain(..) {
+            let mut datagram = self.chunk.split_to(size);
+            datagram.truncate(received.len);
+            datagrams.push((datagram.freeze(), received));
+        }
+        self.chunk.clear();
+        result
+    }
+}
+
+// Event callback registered with `DiscoveryBuilder::on_event`
+type Callback = Box<dyn Fn(&DiscoveryEvent) + Send + Sync>;
+
+// Neighbor decoded by a receive thread, with its sequence number and how
+// and when it was received
+type Decoded = (Neighbor, u16, Received, usize, SystemTime);
+
+// State shared between the service handle and its threads
+struct Shared {
+    transports: Vec<Box<dyn Transport>>,
+    table: Mutex<NeighborTable>,
+    callbacks: Vec<Callback>,
+    subscribers: Mutex<Vec<Sender<DiscoveryEvent>>>,
+    max_packet_size: usize,
+    duplicate_window: Duration,
+    detect_spoofing: bool,
+    clock: Box<dyn Clock>,
+    running: AtomicBool,
+}
+
+impl Shared {
+    fn dispatch(&self, event: DiscoveryEvent) {
+        for callback in &self.callbacks {
+            callback(&event);
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
+    // Receive and decode datagrams from the transport with index `index`,
+    // passing neighbors to the event thread; this never waits on the table
+    // or event consumers
+    fn receive(&self, index: usize, decoded: Sender<Decoded>) {
+        let transport = self.transports[index].as_ref();
+        let mut pool = RecvPool::new(self.max_packet_size);
+        let mut datagrams = Vec::new();
+
+        while self.running.load(Ordering::Relaxed) {
+            // Datagrams before an error are still processed
+            let result = pool.recv_batch(transport, &mut datagrams);
+            let now = self.clock.now();
+
+            for (bytes, received) in datagrams.drain(..) {
+                if let Some((neighbor, sequence)) = decode(bytes, self.max_packet_size) {
+                    if decoded.send((neighbor, sequence, received, index, now)).is_err() {
+                        return;
+                    }
+                }
+            }
+            if let Err(e) = result {
+                recv_backoff(&e);
+            }
+        }
+    }
+
+    // Merge neighbors from all receive threads into the table, expiring
+    // stale ones between them, and dispatch the resulting events in order
+    fn process(&self, decoded: Receiver<Decoded>) {
+        let mut next_expiry = Instant::now() + POLL_INTERVAL;
+        let mut duplicates = DuplicateFilter::new(self.duplicate_window);
+        let mut sequences = self.detect_spoofing.then(SequenceTracker::new);
+
+        while self.running.load(Ordering::Relaxed) {
+            match decoded.recv_timeout(next_expiry.saturating_duration_since(Instant::now())) {
+                Ok((neighbor, sequence, _, _, now))
+                    if neighbor.mac_address.is_some_and(|mac| duplicates.is_duplicate(mac, sequence, now)) => {}
+                Ok((neighbor, sequence, received, transport, now)) => {
+                    let mut table = self.table.lock().unwrap();
+                    let suspicious = sequences.as_mut().and_then(|sequences| sequences.check(&table, &neighbor, sequence, &received, transport, now));
+                    let event = table.update_received(neighbor, &received, now);
+                    drop(table);
+                    for event in suspicious.into_iter().chain(event) {
+                        self.dispatch(event);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if Instant::now() >= next_expiry {
+                let mut table = self.table.lock().unwrap();
+                let events = table.expire(self.clock.now());
+                if let Some(sequences) = &mut sequences {
+                    sequences.prune(table.ttl(), self.clock.now());
+                }
+                drop(table);
+                for event in events {
+                    self.dispatch(event);
+                }
+                duplicates.prune(self.clock.now());
+                next_expiry = Instant::now() + POLL_INTERVAL;
+            }
+        }
+    }
+}
+
+/// Running discovery service.
+///
+/// Each transport has its own thread receiving and decoding datagrams, so a
+/// busy interface does not hold up the others. A single event thread merges
+/// what they receive into the table, expires neighbors which have not been
+/// heard from within the TTL and dispatches the resulting events, so events
+/// are delivered in the order the table changed. The service stops when it
+/// is dropped.
+pub struct Discovery {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Builder structure for a `Discovery` service.
+pub struct DiscoveryBuilder {
+    port: u16,
+    family: AddressFamily,
+    interfaces: Vec<String>,
+    namespace: Option<String>,
+    table: NeighborTable,
+    transports: Vec<Box<dyn Transport>>,
+    callbacks: Vec<Callback>,
+    max_packet_size: usize,
+    duplicate_window: Duration,
+    detect_spoofing: bool,
+    clock: Box<dyn Clock>,
+}
+
+impl Discovery {
+    /// Create a new discovery service builder.
+    pub fn builder() -> DiscoveryBuilder {
+        DiscoveryBuilder {
+            port: MNDP_PORT,
+            family: AddressFamily::Any,
+            interfaces: Vec::new(),
+            namespace: None,
+            table: NeighborTable::new(),
+            transports: Vec::new(),
+            callbacks: Vec::new(),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            duplicate_window: Duration::ZERO,
+            detect_spoofing: false,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Send a solicitation on all transports, prompting neighbors to announce
+    /// themselves. Succeeds if it was sent on at least one transport.
+    pub fn solicit(&self) -> io::Result<()> {
+        let bytes: Bytes = SOLICIT.to_bytes();
+        broadcast_all(self.shared.transports.iter().map(|transport| transport.as_ref()), &bytes)
+    }
+
+    /// Snapshot of all currently known neighbors.
+    pub fn neighbors(&self) -> Vec<DiscoveredNeighbor> {
+        self.shared.table.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Look up a currently known neighbor by MAC address.
+    pub fn neighbor(&self, mac: &MacAddr6) -> Option<DiscoveredNeighbor> {
+        self.shared.table.lock().unwrap().get(mac).cloned()
+    }
+
+    /// Copy of the current neighbor table; e.g. for saving to disk.
+    pub fn table(&self) -> NeighborTable {
+        self.shared.table.lock().unwrap().clone()
+    }
+
+    /// Subscribe to discovery events. The channel is disconnected when the
+    /// service stops.
+    pub fn subscribe(&self) -> Receiver<DiscoveryEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+}
+
+impl Drop for Discovery {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        self.shared.subscribers.lock().unwrap().clear();
+    }
+}
+
+impl DiscoveryBuilder {
+    /// Set the UDP port for the default transports (default `MNDP_PORT`).
+    pub fn port(mut self, value: u16) -> Self {
+        self.port = value;
+        self
+    }
+
+    /// Restrict the default transports to one address family (default
+    /// `AddressFamily::Any`).
+    pub fn address_family(mut self, value: AddressFamily) -> Self {
+        self.family = value;
+        self
+    }
+
+    /// Listen only on the named network interface; e.g. 'ether1'. May be
+    /// called more than once to listen on several interfaces, each with its
+    /// own transports (default all interfaces).
+    pub fn interface<S: Into<String>>(mut self, value: S) -> Self {
+        self.interfaces.push(value.into());
+        self
+    }
+
+    /// Open the default transports in a Linux network namespace, given by
+    /// name or path (see `in_namespace`), instead of the current one.
+    #[cfg(target_os = "linux")]
+    pub fn namespace<S: Into<String>>(mut self, value: S) -> Self {
+        self.namespace = Some(value.into());
+        self
+    }
+
+    /// Set the starting neighbor table; e.g. one loaded from disk. Its TTL
+    /// is used for expiry.
+    pub fn table(mut self, value: NeighborTable) -> Self {
+        self.table = value;
+        self
+    }
+
+    /// Use a custom transport. If any are given, the default UDP transports
+    /// are not created.
+    pub fn transport<T: Transport + 'static>(mut self, value: T) -> Self {
+        self.transports.push(Box::new(value));
+        self
+    }
+
+    /// Set the largest packet accepted; larger datagrams are dropped. This
+    /// also sizes the receive buffer of each transport (default
+    /// `DEFAULT_MAX_PACKET_SIZE`).
+    pub fn max_packet_size(mut self, value: usize) -> Self {
+        self.max_packet_size = value;
+        self
+    }
+
+    /// Treat announcements with the same MAC address and sequence number
+    /// received within `window` of each other as one, ignoring the copies
+    /// devices send on other paths (default zero, which keeps them all).
+    pub fn suppress_duplicates(mut self, window: Duration) -> Self {
+        self.duplicate_window = window;
+        self
+    }
+
+    /// Track the sequence numbers each MAC address announces, and report
+    /// announcements which repeat one or go backwards without a reboot as
+    /// `Suspicious` events; with transports which capture frames (such as
+    /// `RawTransport`), also those whose addresses differ from the frame's
+    /// source. Copies sent on other paths repeat the sequence number, so
+    /// sequence numbers are tracked for each transport and VLAN apart
+    /// (default false).
+    pub fn detect_spoofing(mut self, value: bool) -> Self {
+        self.detect_spoofing = value;
+        self
+    }
+
+    /// Set the clock neighbors are timestamped and expired by (default
+    /// `SystemClock`); e.g. a `MockClock` to test expiry without waiting.
+    pub fn clock<C: Clock + 'static>(mut self, value: C) -> Self {
+        self.clock = Box::new(value);
+        self
+    }
+
+    /// Call `callback` with each discovery event, on the service's event
+    /// thread. Callbacks should return quickly, as they delay later events
+    /// (though not receiving); `Discovery::subscribe` suits slower consumers.
+    pub fn on_event<F: Fn(&DiscoveryEvent) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Open the transports and start the service.
+    ///
+    /// By default an IPv4 and an IPv6 transport are opened for each
+    /// interface; failure to open one of them is tolerated as long as the
+    /// other succeeds. With a single address family, failure to open its
+    /// transport is an error.
+    pub fn start(self) -> io::Result<Discovery> {
+        let mut transports = self.transports;
+        if transports.is_empty() && self.interfaces.is_empty() {
+            transports = default_transports(self.port, self.family, None, self.namespace.as_deref())?;
+        } else if transports.is_empty() {
+            for interface in &self.interfaces {
+                transports.extend(default_transports(self.port, self.family, Some(interface), self.namespace.as_deref())?);
+            }
+        }
+
+        let shared = Arc::new(Shared {
+            transports,
+            table: Mutex::new(self.table),
+            callbacks: self.callbacks,
+            subscribers: Mutex::new(Vec::new()),
+            max_packet_size: self.max_packet_size,
+            duplicate_window: self.duplicate_window,
+            detect_spoofing: self.detect_spoofing,
+            clock: self.clock,
+            running: AtomicBool::new(true),
+        });
+
+        let (sender, decoded) = mpsc::channel();
+        let mut threads = Vec::new();
+        for index in 0..shared.transports.len() {
+            let shared = shared.clone();
+            let sender = sender.clone();
+            threads.push(thread::spawn(move || shared.receive(index, sender)));
+        }
+        let events = shared.clone();
+        threads.push(thread::spawn(move || events.process(decoded)));
+
+        Ok(Discovery { shared, threads })
+    }
+}
+
+/// Find the neighbors on the network in one call: start a discovery service
+/// with the default settings, solicit, listen for `timeout` and stop.
+/// Returns one entry per device that announced itself, sorted by MAC
+/// address.
+pub fn discover(timeout: Duration) -> io::Result<Vec<DiscoveredNeighbor>> {
+    let discovery = Discovery::builder().start()?;
+    discovery.solicit()?;
+    thread::sleep(timeout);
+
+    let mut neighbors = discovery.neighbors();
+    neighbors.sort_by_key(|neighbor| neighbor.neighbor.mac_address);
+    Ok(neighbors)
+}
+
+/// Async version of `discover`. The service runs on its own thread while
+/// the caller waits, so this does not block and works on any executor.
+#[cfg(feature = "async")]
+pub async fn discover_async(timeout: Duration) -> io::Result<Vec<DiscoveredNeighbor>> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(discover(timeout));
+    });
+    receiver.await.unwrap_or_else(|_| Err(io::Error::other("discovery thread panicked")))
+}
+
+#[test]
+fn test_discovery_loopback() {
+    use crate::Neighbor;
+
+    // Send an announcement to a service listening on a loopback transport
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(POLL_INTERVAL)).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let (sender, callback_events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let discovery = Discovery::builder()
+        .transport(UdpTransport::new(socket, addr))
+        .on_event(move |event| sender.lock().unwrap().send(event.name()).unwrap())
+        .start()
+        .unwrap();
+    let events = discovery.subscribe();
+
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .identity("router1")
+        .build();
+    let bytes: Bytes = Packet::from_neighbor(&neighbor).to_bytes();
+    UdpSocket::bind("127.0.0.1:0").unwrap().send_to(&bytes, addr).unwrap();
+
+    match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+        DiscoveryEvent::Added(added) => assert_eq!(added.neighbor, neighbor),
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(callback_events.recv_timeout(Duration::from_secs(5)).unwrap(), "added");
+    assert_eq!(discovery.neighbors().len(), 1);
+}
+
+#[test]
+fn test_discovery_merged_events() {
+    use crate::Neighbor;
+
+    // Announcements received on separate transports appear in one stream
+    let mut builder = Discovery::builder();
+    let mut addrs = Vec::new();
+    for _ in 0..2 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(POLL_INTERVAL)).unwrap();
+        addrs.push(socket.local_addr().unwrap());
+        builder = builder.transport(UdpTransport::new(socket, addrs[addrs.len() - 1]));
+    }
+    let discovery = builder.start().unwrap();
+    let events = discovery.subscribe();
+
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for (index, addr) in addrs.iter().enumerate() {
+        let neighbor = Neighbor::builder().mac_address([0, 0, 0, 0, 0, index as u8 + 1]).build();
+        let bytes: Bytes = Packet::from_neighbor(&neighbor).to_bytes();
+        sender.send_to(&bytes, addr).unwrap();
+    }
+
+    let mut added: Vec<_> = (0..2)
+        .map(|_| match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            DiscoveryEvent::Added(added) => added.neighbor.mac_address.unwrap(),
+            event => panic!("unexpected event {:?}", event),
+        })
+        .collect();
+    added.sort();
+    assert_eq!(added, vec![MacAddr6::new(0, 0, 0, 0, 0, 1), MacAddr6::new(0, 0, 0, 0, 0, 2)]);
+}
+
+#[test]
+fn test_discovery_receive_errors() {
+    use std::sync::atomic::AtomicUsize;
+
+    struct Failing(Arc<AtomicUsize>);
+
+    impl Transport for Failing {
+        fn recv_from(&self, _buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(io::Error::new(io::ErrorKind::NotConnected, "interface went away"))
+        }
+
+        fn broadcast(&self, _buf: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // A persistent error is retried after a pause, not in a busy loop
+    let calls = Arc::new(AtomicUsize::new(0));
+    let discovery = Discovery::builder().transport(Failing(calls.clone())).start().unwrap();
+    thread::sleep(Duration::from_secs(1));
+    drop(discovery);
+    let calls = calls.load(Ordering::Relaxed);
+    assert!((1..=4).contains(&calls), "{}", calls);
+}
+
+#[test]
+fn test_recv_pool_reuse() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let transport = UdpTransport::new(socket, addr);
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut datagrams = Vec::new();
+
+    // Datagrams, in one batch or several, take consecutive slots of a chunk
+    let mut pool = RecvPool::new(DEFAULT_MAX_PACKET_SIZE);
+    sender.send_to(b"first", addr).unwrap();
+    sender.send_to(b"second", addr).unwrap();
+    while datagrams.len() < 2 {
+        pool.recv_batch(&transport, &mut datagrams).unwrap();
+    }
+    let (first, second) = (&datagrams[0], &datagrams[1]);
+    assert_eq!((&first.0[..], &second.0[..], second.1.len), (&b"first"[..], &b"second"[..], 6));
+    assert_eq!(second.0.as_ptr() as usize - first.0.as_ptr() as usize, DEFAULT_MAX_PACKET_SIZE + 1);
+    datagrams.clear();
+
+    // With no room left, a chunk is reused once its datagrams are dropped,
+    // and a new one allocated if they are still held
+    let mut pool = RecvPool::new(RECV_CHUNK_SIZE);
+    sender.send_to(b"first", addr).unwrap();
+    pool.recv_batch(&transport, &mut datagrams).unwrap();
+    let start = datagrams[0].0.as_ptr();
+    datagrams.clear();
+    sender.send_to(b"second", addr).unwrap();
+    pool.recv_batch(&transport, &mut datagrams).unwrap();
+    assert_eq!(datagrams[0].0.as_ptr(), start);
+    sender.send_to(b"third", addr).unwrap();
+    pool.recv_batch(&transport, &mut datagrams).unwrap();
+    assert_eq!(&datagrams[1].0[..], b"third");
+    assert_ne!(datagrams[1].0.as_ptr(), start);
+}
+
+#[test]
+fn test_open_all_family() {
+    // Port 0 binds an ephemeral port; IPv6 may be unavailable
+    let transports = UdpTransport::open_all(0, AddressFamily::Ipv4);
+    assert_eq!(transports.len(), 1);
+    assert!(transports[0].as_ref().unwrap().socket().local_addr().unwrap().is_ipv4());
+    assert_eq!(UdpTransport::open_all(0, AddressFamily::Ipv6).len(), 1);
+    assert_eq!(UdpTransport::open_all(0, AddressFamily::Any).len(), 2);
+}
+
+#[test]
+fn test_from_socket() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let transport = UdpTransport::from_socket(socket).unwrap();
+    assert_eq!(transport.destination, SocketAddr::V4(ipv4_destination(port)));
+    assert!(transport.socket().broadcast().unwrap());
+}
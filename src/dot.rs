@@ -0,0 +1,99 @@
+//! Graphviz (DOT) output of the local host's interfaces and the neighbors
+//! heard on each.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::{sanitize, DiscoveredNeighbor};
+
+// A value from the network escaped for a DOT string, with control
+// characters escaped first so they cannot end the label or the line
+fn escape(value: &str) -> String {
+    sanitize(value).replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+// Label lines joined with DOT's centred line break, skipping missing values
+fn label<I: IntoIterator<Item = Option<String>>>(lines: I) -> String {
+    let lines: Vec<String> = lines.into_iter().flatten().map(|line| escape(&line)).collect();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+/// Format an undirected Graphviz graph of the topology discovery found: a
+/// node for the local host named `host`, one for each of its `interfaces`
+/// (by index and name) and one for each neighbor, joined to the interface
+/// its `scope_id` says it was heard on. Edges to neighbors are labelled with
+/// the neighbor's interface name and addresses. Interfaces a neighbor was
+/// heard on but which are not listed are named by index, and neighbors heard
+/// on an unknown interface are joined to an 'unknown interface' node.
+pub fn topology_dot(host: &str, interfaces: &[(u32, String)], neighbors: &[DiscoveredNeighbor]) -> String {
+    let mut names: BTreeMap<Option<u32>, String> = interfaces.iter()
+        .map(|(index, name)| (Some(*index), name.clone()))
+        .collect();
+    for neighbor in neighbors {
+        names.entry(neighbor.scope_id).or_insert_with(|| match neighbor.scope_id {
+            Some(index) => format!("interface {}", index),
+            None => String::from("unknown interface"),
+        });
+    }
+
+    let mut dot = String::from("graph mndp {\n    node [shape=box];\n");
+    let _ = writeln!(dot, "    host [label={}, shape=box3d];", quote(host));
+    for (index, name) in &names {
+        let id = index.map_or_else(|| String::from("unknown"), |index| format!("if{}", index));
+        let _ = writeln!(dot, "    {} [label={}, shape=ellipse];", id, quote(name));
+        let _ = writeln!(dot, "    host -- {};", id);
+    }
+
+    for (number, discovered) in neighbors.iter().enumerate() {
+        let neighbor = &discovered.neighbor;
+        let interface = discovered.scope_id.map_or_else(|| String::from("unknown"), |index| format!("if{}", index));
+        let _ = writeln!(dot, "    n{} [label={}];", number, label(vec![
+            neighbor.identity.clone(),
+            neighbor.mac_address.map(|mac| mac.to_string()),
+            neighbor.board.clone(),
+        ]));
+        let _ = writeln!(dot, "    {} -- n{} [label={}];", interface, number, label(vec![
+            neighbor.interface_name.clone(),
+            neighbor.ipv4_address.map(|addr| addr.to_string()),
+            neighbor.ipv6_address.map(|addr| addr.to_string()),
+        ]));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[test]
+fn test_topology_dot() {
+    use std::time::UNIX_EPOCH;
+    use crate::Neighbor;
+
+    let heard = |neighbor: Neighbor, scope_id| DiscoveredNeighbor {
+        neighbor,
+        first_seen: UNIX_EPOCH,
+        last_seen: UNIX_EPOCH,
+        vlan: None,
+        scope_id,
+    };
+    let neighbors = [
+        heard(Neighbor::builder()
+            .identity("core \"1\"")
+            .mac_address([0, 0, 0, 0, 0, 1])
+            .interface_name("ether1")
+            .ipv4_address([192, 0, 2, 1])
+            .build(), Some(2)),
+        heard(Neighbor::builder().identity("edge\x1b").build(), None),
+    ];
+    let dot = topology_dot("host1", &[(2, String::from("eth0")), (3, String::from("eth1"))], &neighbors);
+
+    assert!(dot.starts_with("graph mndp {\n"));
+    assert!(dot.contains("    host [label=\"host1\", shape=box3d];\n"));
+    assert!(dot.contains("    if3 [label=\"eth1\", shape=ellipse];\n    host -- if3;\n"));
+    assert!(dot.contains("    n0 [label=\"core \\\"1\\\"\\n00:00:00:00:00:01\"];\n"));
+    assert!(dot.contains("    if2 -- n0 [label=\"ether1\\n192.0.2.1\"];\n"));
+    assert!(dot.contains("    unknown [label=\"unknown interface\", shape=ellipse];\n"));
+    assert!(dot.contains("    n1 [label=\"edge\\\\x1b\"];\n    unknown -- n1 [label=\"\"];\n"));
+}
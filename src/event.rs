@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::{DiscoveredNeighbor, NeighborField};
+
+/// Change in the set of known neighbors, as reported by `NeighborTable`.
+///
+/// This is the event schema shared by all consumers: `Discovery` callbacks
+/// and subscriber channels, the CLI's NDJSON output and webhooks. Serialized,
+/// each event is the `DiscoveredNeighbor` object with an `event` member
+/// naming the variant; `updated` events also list the `changed` fields.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event", rename_all = "snake_case"))]
+pub enum DiscoveryEvent {
+    /// A neighbor was heard from for the first time.
+    Added(DiscoveredNeighbor),
+    /// A known neighbor announced different information (other than uptime).
+    Updated {
+        /// The neighbor with the updated information.
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        neighbor: DiscoveredNeighbor,
+        /// Fields which changed since the previous announcement.
+        changed: Vec<NeighborField>,
+    },
+    /// A neighbor was not heard from within the TTL and was removed.
+    Expired(DiscoveredNeighbor),
+    /// A known neighbor announced a lower uptime than before.
+    Rebooted(DiscoveredNeighbor),
+    /// An announcement looked spoofed or replayed; only reported when
+    /// enabled with `DiscoveryBuilder::detect_spoofing`. The announcement is
+    /// still applied, and followed by its own event if it changed anything.
+    Suspicious {
+        /// The neighbor as announced.
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        neighbor: DiscoveredNeighbor,
+        /// Why the announcement looked suspicious.
+        reason: Suspicion,
+    },
+}
+
+/// Reason an announcement was reported by a `Suspicious` event.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Suspicion {
+    /// The MAC address announced the same sequence number again.
+    RepeatedSequence,
+    /// The MAC address announced a lower sequence number than before, without
+    /// its uptime showing a reboot.
+    OutOfOrderSequence,
+    /// The announced MAC address is not the source of the frame carrying it.
+    MacMismatch,
+    /// The announced IPv4 address is not the source of the frame carrying it.
+    IpMismatch,
+}
+
+impl Suspicion {
+    /// Short name of the reason, as serialized; e.g. 'mac_mismatch'.
+    pub fn name(self) -> &'static str {
+        use Suspicion::*;
+        match self {
+            RepeatedSequence => "repeated_sequence",
+            OutOfOrderSequence => "out_of_order_sequence",
+            MacMismatch => "mac_mismatch",
+            IpMismatch => "ip_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl DiscoveryEvent {
+    /// The neighbor this event refers to.
+    pub fn neighbor(&self) -> &DiscoveredNeighbor {
+        use DiscoveryEvent::*;
+        match self {
+            Added(n) | Updated { neighbor: n, .. } | Expired(n) | Rebooted(n) | Suspicious { neighbor: n, .. } => n,
+        }
+    }
+
+    /// Fields which changed, for an `Updated` event; otherwise empty.
+    pub fn changed(&self) -> &[NeighborField] {
+        match self {
+            DiscoveryEvent::Updated { changed, .. } => changed,
+            _ => &[],
+        }
+    }
+
+    /// Short lowercase name of the event type; e.g. 'added'.
+    pub fn name(&self) -> &'static str {
+        use DiscoveryEvent::*;
+        match self {
+            Added(_) => "added",
+            Updated { .. } => "updated",
+            Expired(_) => "expired",
+            Rebooted(_) => "rebooted",
+            Suspicious { .. } => "suspicious",
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_event_serde() {
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::Neighbor;
+
+    let event = DiscoveryEvent::Updated {
+        neighbor: DiscoveredNeighbor {
+            neighbor: Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).version("7.1").build(),
+            first_seen: UNIX_EPOCH + Duration::from_secs(10),
+            last_seen: UNIX_EPOCH + Duration::from_secs(20),
+            vlan: None,
+            scope_id: None,
+        },
+        changed: vec![NeighborField::Version],
+    };
+
+    // The neighbor is flattened into the event object like other variants
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["event"], "updated");
+    assert_eq!(json["neighbor"]["version"], "7.1");
+    assert_eq!(json["first_seen"], "1970-01-01T00:00:10Z");
+    assert_eq!(json["changed"], serde_json::json!(["version"]));
+    assert_eq!(serde_json::from_value::<DiscoveryEvent>(json).unwrap(), event);
+}
@@ -0,0 +1,174 @@
+//! gRPC service exposing a `Discovery` service (see `proto/mndp.proto`).
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::timestamp::unix_secs;
+use crate::{DiscoveredNeighbor, Discovery, DiscoveryEvent};
+
+/// Generated protocol buffer types, client and server.
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("mndp");
+}
+
+use proto::discovery_server::DiscoveryServer;
+
+// How often event forwarding checks whether the client has gone away
+const CLOSED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Implementation of the generated `Discovery` gRPC service.
+#[derive(Clone)]
+pub struct GrpcService {
+    discovery: Arc<Discovery>,
+}
+
+impl GrpcService {
+    /// Create a service backed by `discovery`.
+    pub fn new(discovery: Arc<Discovery>) -> GrpcService {
+        GrpcService { discovery }
+    }
+
+    /// Wrap the service for adding to a `tonic::transport::Server`.
+    pub fn into_server(self) -> DiscoveryServer<GrpcService> {
+        DiscoveryServer::new(self)
+    }
+
+    /// Serve the neighbors known to `discovery` on `addr` until the future
+    /// is dropped or fails.
+    pub async fn serve(addr: SocketAddr, discovery: Arc<Discovery>) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(GrpcService::new(discovery).into_server())
+            .serve(addr)
+            .await
+    }
+}
+
+#[tonic::async_trait]
+impl proto::discovery_server::Discovery for GrpcService {
+    async fn list_neighbors(&self, _: Request<proto::ListNeighborsRequest>)
+        -> Result<Response<proto::ListNeighborsResponse>, Status>
+    {
+        let mut neighbors = self.discovery.neighbors();
+        neighbors.sort_by_key(|n| n.neighbor.mac_address);
+        Ok(Response::new(proto::ListNeighborsResponse {
+            neighbors: neighbors.iter().map(proto::Neighbor::from).collect(),
+        }))
+    }
+
+    type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+    async fn watch_events(&self, _: Request<proto::WatchEventsRequest>)
+        -> Result<Response<Self::WatchEventsStream>, Status>
+    {
+        let events = self.discovery.subscribe();
+        let (sender, receiver) = mpsc::channel(16);
+
+        // Subscriptions are blocking channels, so forward from a blocking task
+        tokio::task::spawn_blocking(move || loop {
+            match events.recv_timeout(CLOSED_CHECK_INTERVAL) {
+                Ok(event) => {
+                    if sender.blocking_send(Ok(proto::Event::from(&event))).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) if !sender.is_closed() => continue,
+                Err(_) => return,
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+    async fn solicit_now(&self, _: Request<proto::SolicitNowRequest>)
+        -> Result<Response<proto::SolicitNowResponse>, Status>
+    {
+        self.discovery.solicit()
+            .map(|_| Response::new(proto::SolicitNowResponse {}))
+            .map_err(|e| Status::unavailable(e.to_string()))
+    }
+}
+
+impl From<&DiscoveredNeighbor> for proto::Neighbor {
+    fn from(discovered: &DiscoveredNeighbor) -> proto::Neighbor {
+        let neighbor = &discovered.neighbor;
+        proto::Neighbor {
+            board: neighbor.board.clone(),
+            identity: neighbor.identity.clone(),
+            interface_name: neighbor.interface_name.clone(),
+            ipv4_address: neighbor.ipv4_address.map(|a| a.to_string()),
+            ipv6_address: neighbor.ipv6_address.map(|a| a.to_string()),
+            mac_address: neighbor.mac_address.map(|a| a.to_string()),
+            platform: neighbor.platform.clone(),
+            software_id: neighbor.software_id.clone(),
+            unpack: neighbor.unpack.map(|u| u.to_string()),
+            uptime_secs: neighbor.uptime.map(|d| d.as_secs()),
+            version: neighbor.version.clone(),
+            first_seen: unix_secs(discovered.first_seen),
+            last_seen: unix_secs(discovered.last_seen),
+            vlan: discovered.vlan.map(u32::from),
+            scope_id: discovered.scope_id,
+        }
+    }
+}
+
+impl From<&DiscoveryEvent> for proto::Event {
+    fn from(event: &DiscoveryEvent) -> proto::Event {
+        use proto::event::Kind;
+        let kind = match event {
+            DiscoveryEvent::Added(_) => Kind::Added,
+            DiscoveryEvent::Updated { .. } => Kind::Updated,
+            DiscoveryEvent::Expired(_) => Kind::Expired,
+            DiscoveryEvent::Rebooted(_) => Kind::Rebooted,
+            DiscoveryEvent::Suspicious { .. } => Kind::Suspicious,
+        };
+        let reason = match event {
+            DiscoveryEvent::Suspicious { reason, .. } => Some(reason.name().to_string()),
+            _ => None,
+        };
+        proto::Event {
+            kind: kind.into(),
+            neighbor: Some(event.neighbor().into()),
+            changed: event.changed().iter().map(|field| field.name().to_string()).collect(),
+            reason,
+        }
+    }
+}
+
+#[test]
+fn test_grpc_neighbor_conversion() {
+    use std::time::UNIX_EPOCH;
+
+    use crate::{Neighbor, Unpack};
+
+    let discovered = DiscoveredNeighbor {
+        neighbor: Neighbor::builder()
+            .mac_address([0, 0, 0, 0, 0, 1])
+            .ipv4_address([192, 168, 88, 1])
+            .unpack(Unpack::Simple)
+            .uptime(Duration::from_secs(60))
+            .build(),
+        first_seen: UNIX_EPOCH + Duration::from_secs(10),
+        last_seen: UNIX_EPOCH + Duration::from_secs(20),
+        vlan: None,
+        scope_id: None,
+    };
+    let event = proto::Event::from(&DiscoveryEvent::Rebooted(discovered));
+    assert_eq!(event.kind(), proto::event::Kind::Rebooted);
+
+    let neighbor = event.neighbor.unwrap();
+    assert_eq!(neighbor.mac_address.as_deref(), Some("00:00:00:00:00:01"));
+    assert_eq!(neighbor.ipv4_address.as_deref(), Some("192.168.88.1"));
+    assert_eq!(neighbor.unpack.as_deref(), Some("simple"));
+    assert_eq!(neighbor.uptime_secs, Some(60));
+    assert_eq!((neighbor.first_seen, neighbor.last_seen), (10, 20));
+    assert_eq!(neighbor.identity, None);
+}
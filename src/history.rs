@@ -0,0 +1,313 @@
+//! Durable discovery history in a SQLite database.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use macaddr::MacAddr6;
+use rusqlite::{params, Connection, Params};
+
+use crate::timestamp::unix_secs;
+use crate::{DiscoveredNeighbor, DiscoveryEvent, Packet};
+
+// Schema, created if missing. Neighbors are stored as an MNDP packet, like
+// `NeighborTable::save`, plus columns for the fields most often queried.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS neighbors (
+        mac TEXT PRIMARY KEY,
+        identity TEXT,
+        platform TEXT,
+        version TEXT,
+        board TEXT,
+        ipv4_address TEXT,
+        ipv6_address TEXT,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL,
+        packet BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sightings (
+        id INTEGER PRIMARY KEY,
+        mac TEXT NOT NULL REFERENCES neighbors (mac),
+        time INTEGER NOT NULL,
+        event TEXT NOT NULL,
+        changed TEXT,
+        uptime INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS sightings_mac_time ON sightings (mac, time);
+";
+
+// How long to wait for another connection's lock, e.g. a CLI query while
+// the daemon writes
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error from the history database.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HistoryError {
+    message: String,
+}
+
+impl HistoryError {
+    fn new<S: Into<String>>(message: S) -> HistoryError {
+        HistoryError { message: message.into() }
+    }
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "history database: {}", self.message)
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(e: rusqlite::Error) -> HistoryError {
+        HistoryError::new(e.to_string())
+    }
+}
+
+/// A discovery event recorded for a neighbor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sighting {
+    /// Time of the event: when the announcement was received, or for
+    /// `expired` when the neighbor was expired.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::rfc3339"))]
+    pub time: SystemTime,
+    /// Event name; e.g. 'added' (see `DiscoveryEvent::name`).
+    pub event: String,
+    /// Names of the changed fields, for `updated` events; the reason, for
+    /// `suspicious` events.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub changed: Vec<String>,
+    /// Uptime the neighbor announced.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::secs"))]
+    pub uptime: Option<Duration>,
+}
+
+/// History of discovered neighbors in a SQLite database: a `neighbors` table
+/// with each neighbor's latest information and first and last sighting, and
+/// a `sightings` table with every discovery event. Times are stored as whole
+/// seconds since the Unix epoch, so the database can also be queried
+/// directly.
+pub struct History {
+    // Locked for the whole of each call, so transactions from different
+    // threads don't interleave on the one connection
+    connection: Mutex<Connection>,
+}
+
+impl History {
+    /// Open the database at `path`, creating it and its tables if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<History, HistoryError> {
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(History { connection: Mutex::new(connection) })
+    }
+
+    /// Record a discovery event, updating the neighbor and adding a
+    /// sighting. `suspicious` events only add a sighting, with the reason as
+    /// its changed field, so a spoofer cannot rewrite the neighbor.
+    pub fn record(&self, event: &DiscoveryEvent) -> Result<(), HistoryError> {
+        let discovered = event.neighbor();
+        let mac = match discovered.neighbor.mac_address {
+            Some(mac) => mac.to_string(),
+            None => return Ok(()),
+        };
+        let time = match event {
+            DiscoveryEvent::Expired(_) => SystemTime::now(),
+            _ => discovered.last_seen,
+        };
+        let changed = match event {
+            DiscoveryEvent::Suspicious { reason, .. } => reason.name().to_string(),
+            _ => event.changed().iter().map(|field| field.name()).collect::<Vec<_>>().join(","),
+        };
+
+        // Rolled back if dropped before committing
+        let mut db = self.connection.lock().unwrap();
+        let transaction = db.transaction()?;
+        if !matches!(event, DiscoveryEvent::Suspicious { .. }) {
+            upsert(&transaction, &mac, discovered)?;
+        }
+        transaction.execute(
+            "INSERT INTO sightings (mac, time, event, changed, uptime) VALUES (?, ?, ?, ?, ?)",
+            params![
+                mac,
+                unix_secs(time) as i64,
+                event.name(),
+                Some(changed).filter(|changed| !changed.is_empty()),
+                discovered.neighbor.uptime.map(|uptime| uptime.as_secs() as i64),
+            ],
+        )?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Update the last-seen time of a neighbor without adding a sighting;
+    /// e.g. periodically for neighbors still being heard from, which only
+    /// produce events when they change.
+    pub fn record_seen(&self, discovered: &DiscoveredNeighbor) -> Result<(), HistoryError> {
+        match discovered.neighbor.mac_address {
+            Some(mac) => upsert(&self.connection.lock().unwrap(), &mac.to_string(), discovered),
+            None => Ok(()),
+        }
+    }
+
+    /// All neighbors ever recorded, with their latest information, sorted by
+    /// MAC address.
+    pub fn neighbors(&self) -> Result<Vec<DiscoveredNeighbor>, HistoryError> {
+        let sql = "SELECT packet, first_seen, last_seen FROM neighbors ORDER BY mac";
+        select_neighbors(&self.connection.lock().unwrap(), sql, [])
+    }
+
+    /// Look up a recorded neighbor by MAC address; e.g. for when it was last
+    /// seen.
+    pub fn neighbor(&self, mac: &MacAddr6) -> Result<Option<DiscoveredNeighbor>, HistoryError> {
+        let sql = "SELECT packet, first_seen, last_seen FROM neighbors WHERE mac = ?";
+        Ok(select_neighbors(&self.connection.lock().unwrap(), sql, [mac.to_string()])?.pop())
+    }
+
+    /// Sightings of a neighbor, oldest first.
+    pub fn sightings(&self, mac: &MacAddr6) -> Result<Vec<Sighting>, HistoryError> {
+        let db = self.connection.lock().unwrap();
+        let mut stmt = db.prepare("SELECT time, event, changed, uptime FROM sightings WHERE mac = ? ORDER BY time, id")?;
+        let rows = stmt.query_map([mac.to_string()], |row| {
+            Ok(Sighting {
+                time: unix_time(row.get(0)?),
+                event: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                changed: row.get::<_, Option<String>>(2)?
+                    .map(|changed| changed.split(',').map(String::from).collect())
+                    .unwrap_or_default(),
+                uptime: row.get::<_, Option<i64>>(3)?.map(|secs| Duration::from_secs(secs.max(0) as u64)),
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+fn upsert(db: &Connection, mac: &str, discovered: &DiscoveredNeighbor) -> Result<(), HistoryError> {
+    let neighbor = &discovered.neighbor;
+    let packet: Bytes = Packet::from_neighbor(neighbor).to_bytes();
+    db.execute(
+        "INSERT INTO neighbors
+            (mac, identity, platform, version, board, ipv4_address, ipv6_address, first_seen, last_seen, packet)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (mac) DO UPDATE SET
+            identity = excluded.identity, platform = excluded.platform, version = excluded.version,
+            board = excluded.board, ipv4_address = excluded.ipv4_address,
+            ipv6_address = excluded.ipv6_address, packet = excluded.packet,
+            first_seen = min(first_seen, excluded.first_seen),
+            last_seen = max(last_seen, excluded.last_seen)",
+        params![
+            mac,
+            neighbor.identity,
+            neighbor.platform,
+            neighbor.version,
+            neighbor.board,
+            neighbor.ipv4_address.map(|addr| addr.to_string()),
+            neighbor.ipv6_address.map(|addr| addr.to_string()),
+            unix_secs(discovered.first_seen) as i64,
+            unix_secs(discovered.last_seen) as i64,
+            &packet[..],
+        ],
+    )?;
+    Ok(())
+}
+
+fn select_neighbors<P: Params>(db: &Connection, sql: &str, params: P) -> Result<Vec<DiscoveredNeighbor>, HistoryError> {
+    let mut stmt = db.prepare(sql)?;
+    let mut rows = stmt.query(params)?;
+    let mut neighbors = Vec::new();
+    while let Some(row) = rows.next()? {
+        let packet: Vec<u8> = row.get::<_, Option<Vec<u8>>>(0)?.unwrap_or_default();
+        let packet = Packet::from_bytes(Bytes::from(packet))
+            .map_err(|_| HistoryError::new("invalid packet in neighbors table"))?;
+        neighbors.push(DiscoveredNeighbor {
+            neighbor: packet.to_neighbor(),
+            first_seen: unix_time(row.get(1)?),
+            last_seen: unix_time(row.get(2)?),
+            vlan: None,
+            scope_id: None,
+        });
+    }
+    Ok(neighbors)
+}
+
+fn unix_time(secs: Option<i64>) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs.unwrap_or(0).max(0) as u64)
+}
+
+#[test]
+fn test_history_record() {
+    use crate::{Neighbor, NeighborField};
+
+    let path = std::env::temp_dir().join(format!("mndp-history-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let history = History::open(&path).unwrap();
+
+    let mac = MacAddr6::new(0, 0, 0, 0, 0, 1);
+    let mut discovered = DiscoveredNeighbor {
+        neighbor: Neighbor::builder().mac_address(mac).identity("router1").version("7.11").build(),
+        first_seen: UNIX_EPOCH + Duration::from_secs(100),
+        last_seen: UNIX_EPOCH + Duration::from_secs(100),
+        vlan: None,
+        scope_id: None,
+    };
+    history.record(&DiscoveryEvent::Added(discovered.clone())).unwrap();
+
+    discovered.neighbor.version = Some(String::from("7.12"));
+    discovered.last_seen = UNIX_EPOCH + Duration::from_secs(200);
+    history.record(&DiscoveryEvent::Updated { neighbor: discovered.clone(), changed: vec![NeighborField::Version] }).unwrap();
+    discovered.last_seen = UNIX_EPOCH + Duration::from_secs(300);
+    history.record_seen(&discovered).unwrap();
+
+    // Reopening finds the latest information and every sighting
+    drop(history);
+    let history = History::open(&path).unwrap();
+    assert_eq!(history.neighbor(&mac).unwrap(), Some(discovered.clone()));
+    assert_eq!(history.neighbors().unwrap(), vec![discovered]);
+    let sightings = history.sightings(&mac).unwrap();
+    assert_eq!(sightings.iter().map(|s| s.event.as_str()).collect::<Vec<_>>(), ["added", "updated"]);
+    assert_eq!(sightings[1].time, UNIX_EPOCH + Duration::from_secs(200));
+    assert_eq!(sightings[1].changed, ["version"]);
+    assert_eq!(history.neighbor(&MacAddr6::new(0, 0, 0, 0, 0, 2)).unwrap(), None);
+
+    drop(history);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_history_threads() {
+    use std::sync::Arc;
+    use crate::Neighbor;
+
+    let path = std::env::temp_dir().join(format!("mndp-history-threads-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let history = Arc::new(History::open(&path).unwrap());
+
+    // Transactions from several threads on the one connection don't overlap
+    let threads: Vec<_> = (0..4u8).map(|thread| {
+        let history = history.clone();
+        std::thread::spawn(move || {
+            for index in 0..25u8 {
+                let neighbor = Neighbor::builder().mac_address([0, 0, 0, 0, thread, index]).build();
+                let discovered = DiscoveredNeighbor {
+                    neighbor,
+                    first_seen: UNIX_EPOCH,
+                    last_seen: UNIX_EPOCH,
+                    vlan: None,
+                    scope_id: None,
+                };
+                history.record(&DiscoveryEvent::Added(discovered)).unwrap();
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(history.neighbors().unwrap().len(), 100);
+
+    drop(history);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,214 @@
+//! Minimal HTTP server exposing the neighbor table of a `Discovery` service.
+//!
+//! * `GET /neighbors` - JSON array of all known neighbors
+//! * `GET /neighbors/<mac>` - JSON object for a single neighbor
+//! * `GET /events` - discovery events as server-sent events
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use macaddr::MacAddr6;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{Discovery, DiscoveryEvent};
+
+// Interval for comments sent to keep idle event streams open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// Most requests handled at once, each on its own thread; more are refused
+// until one finishes, so clients cannot exhaust the process's threads
+const MAX_HANDLERS: usize = 64;
+
+/// Running HTTP server. The server stops when it is dropped.
+pub struct HttpServer {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Start serving the neighbors known to `discovery` on `addr`.
+    ///
+    /// Each request is handled on its own thread, up to 64 at once
+    /// (including open event streams); others get a 503 response.
+    pub fn start<A: ToSocketAddrs>(addr: A, discovery: Arc<Discovery>) -> io::Result<HttpServer> {
+        let server = Server::http(addr)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let server = Arc::new(server);
+
+        let incoming = server.clone();
+        let thread = thread::spawn(move || {
+            let handlers = Arc::new(AtomicUsize::new(0));
+            for request in incoming.incoming_requests() {
+                if handlers.fetch_add(1, Ordering::SeqCst) >= MAX_HANDLERS {
+                    handlers.fetch_sub(1, Ordering::SeqCst);
+                    let _ = request.respond(Response::empty(StatusCode(503)));
+                    continue;
+                }
+
+                // Event streams tie up their thread for as long as the client
+                // stays connected
+                let discovery = discovery.clone();
+                let handlers = handlers.clone();
+                thread::spawn(move || {
+                    handle(request, &discovery);
+                    handlers.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        Ok(HttpServer {
+            server,
+            thread: Some(thread),
+        })
+    }
+
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn handle(request: Request, discovery: &Discovery) {
+    if *request.method() != Method::Get {
+        let _ = request.respond(Response::empty(StatusCode(405)));
+        return;
+    }
+
+    let path = request.url().split('?').next().unwrap_or("").trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+
+    let _ = match segments.as_slice() {
+        ["neighbors"] => {
+            let mut neighbors = discovery.neighbors();
+            neighbors.sort_by_key(|n| n.neighbor.mac_address);
+            request.respond(json(&neighbors))
+        }
+        ["neighbors", mac] => match mac.parse::<MacAddr6>().ok().and_then(|mac| discovery.neighbor(&mac)) {
+            Some(neighbor) => request.respond(json(&neighbor)),
+            None => request.respond(Response::empty(StatusCode(404))),
+        },
+        ["events"] => stream_events(request.into_writer(), discovery.subscribe()),
+        _ => request.respond(Response::empty(StatusCode(404))),
+    };
+}
+
+fn json<T: serde::Serialize>(value: &T) -> Response<io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_data(serde_json::to_vec(value).unwrap_or_default())
+        .with_header(header)
+}
+
+// Write events as they occur until the client disconnects or the service
+// stops. Written directly to the connection, since responses are otherwise
+// buffered.
+fn stream_events(mut writer: Box<dyn Write + Send>, events: Receiver<DiscoveryEvent>) -> io::Result<()> {
+    writer.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+    writer.flush()?;
+
+    loop {
+        match events.recv_timeout(KEEP_ALIVE_INTERVAL) {
+            Ok(event) => {
+                let data = serde_json::to_string(&event).unwrap_or_default();
+                write!(writer, "event: {}\ndata: {}\n\n", event.name(), data)?;
+            }
+            Err(RecvTimeoutError::Timeout) => writer.write_all(b":\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        writer.flush()?;
+    }
+}
+
+#[test]
+fn test_http_neighbors() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{TcpStream, UdpSocket};
+    use bytes::Bytes;
+    use crate::{Neighbor, Packet, UdpTransport};
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let discovery = Arc::new(Discovery::builder()
+        .transport(UdpTransport::new(socket, addr))
+        .start()
+        .unwrap());
+    let events = discovery.subscribe();
+
+    let neighbor = Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).identity("router1").build();
+    let bytes: Bytes = Packet::from_neighbor(&neighbor).to_bytes();
+    UdpSocket::bind("127.0.0.1:0").unwrap().send_to(&bytes, addr).unwrap();
+    events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    let server = HttpServer::start("127.0.0.1:0", discovery).unwrap();
+    let get = |path: &str| {
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = get("/neighbors");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains(r#""identity":"router1""#));
+    assert!(get("/neighbors/00:00:00:00:00:01").starts_with("HTTP/1.1 200"));
+    assert!(get("/neighbors/00:00:00:00:00:02").starts_with("HTTP/1.1 404"));
+
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    write!(stream, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+
+    let updated = Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).identity("router2").build();
+    let bytes: Bytes = Packet::from_neighbor(&updated).to_bytes();
+    UdpSocket::bind("127.0.0.1:0").unwrap().send_to(&bytes, addr).unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "event: updated\n");
+}
+
+#[test]
+fn test_http_max_handlers() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+    use crate::MockTransport;
+
+    let discovery = Arc::new(Discovery::builder().transport(MockTransport::new()).start().unwrap());
+    let server = HttpServer::start("127.0.0.1:0", discovery).unwrap();
+    let connect = |path: &str| {
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+        BufReader::new(stream)
+    };
+
+    // Open event streams hold every handler, so further requests are refused
+    let streams: Vec<_> = (0..MAX_HANDLERS).map(|_| {
+        let mut reader = connect("/events");
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"), "{}", line);
+        reader
+    }).collect();
+    let mut response = String::new();
+    connect("/neighbors").read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    drop(streams);
+}
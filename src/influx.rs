@@ -0,0 +1,175 @@
+//! InfluxDB line protocol output of neighbor observations.
+
+use std::fmt::Write as _;
+#[cfg(feature = "influx")]
+use std::io;
+#[cfg(feature = "influx")]
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use crate::timestamp::unix_secs;
+use crate::DiscoveredNeighbor;
+
+/// Measurement name used by the `mndp` tool for neighbor observations.
+pub const INFLUX_MEASUREMENT: &str = "mndp_neighbor";
+
+impl DiscoveredNeighbor {
+    /// Format the neighbor as one line of InfluxDB line protocol, without a
+    /// trailing newline. The `mac`, `identity`, `board` and `interface` tags
+    /// identify it; the fields are `uptime` (seconds), `version` and the
+    /// `first_seen` and `last_seen` Unix times. The timestamp is `last_seen`,
+    /// in nanoseconds.
+    pub fn to_line_protocol(&self, measurement: &str) -> String {
+        let neighbor = &self.neighbor;
+        let mut line = escape(measurement, ", ");
+
+        let tags = [
+            ("mac", neighbor.mac_address.map(|mac| mac.to_string())),
+            ("identity", neighbor.identity.clone()),
+            ("board", neighbor.board.clone()),
+            ("interface", neighbor.interface_name.clone()),
+        ];
+        // Empty tag values are not allowed
+        for (key, value) in &tags {
+            if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+                let _ = write!(line, ",{}={}", key, escape(value, ",= "));
+            }
+        }
+
+        let mut fields = Vec::new();
+        if let Some(uptime) = neighbor.uptime {
+            fields.push(format!("uptime={}i", uptime.as_secs()));
+        }
+        if let Some(version) = &neighbor.version {
+            fields.push(format!("version=\"{}\"", escape(version, "\"")));
+        }
+        fields.push(format!("first_seen={}i", unix_secs(self.first_seen)));
+        fields.push(format!("last_seen={}i", unix_secs(self.last_seen)));
+
+        let timestamp = self.last_seen.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let _ = write!(line, " {} {}", fields.join(","), timestamp);
+        line
+    }
+}
+
+// Backslash-escape `special` characters and backslashes; newlines cannot be
+// escaped, so are replaced by spaces (escaped if spaces are special)
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        let c = if c == '\n' || c == '\r' { ' ' } else { c };
+        if c == '\\' || special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Writer which sends neighbor observations to an InfluxDB HTTP write
+/// endpoint.
+#[cfg(feature = "influx")]
+#[derive(Debug)]
+pub struct InfluxWriter {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+    measurement: String,
+}
+
+/// Builder structure for an `InfluxWriter`.
+#[cfg(feature = "influx")]
+#[derive(Clone, Debug)]
+pub struct InfluxWriterBuilder {
+    url: String,
+    token: Option<String>,
+    measurement: String,
+    timeout: Duration,
+}
+
+#[cfg(feature = "influx")]
+impl InfluxWriter {
+    /// Create a new writer builder which will POST to `url`, the full write
+    /// endpoint including its query; e.g.
+    /// 'http://localhost:8086/api/v2/write?org=lab&bucket=mndp'.
+    pub fn builder<S: Into<String>>(url: S) -> InfluxWriterBuilder {
+        InfluxWriterBuilder {
+            url: url.into(),
+            token: None,
+            measurement: String::from(INFLUX_MEASUREMENT),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Write one point per neighbor in a single request.
+    pub fn write(&self, neighbors: &[DiscoveredNeighbor]) -> io::Result<()> {
+        let body: String = neighbors.iter()
+            .map(|neighbor| neighbor.to_line_protocol(&self.measurement) + "\n")
+            .collect();
+
+        let mut request = self.agent.post(&self.url)
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Token {}", token));
+        }
+        request.send_string(&body).map(|_| ()).map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+#[cfg(feature = "influx")]
+impl InfluxWriterBuilder {
+    /// Set the API token sent with each request.
+    pub fn token<S: Into<String>>(mut self, value: S) -> Self {
+        self.token = Some(value.into());
+        self
+    }
+
+    /// Set the measurement name (default `INFLUX_MEASUREMENT`).
+    pub fn measurement<S: Into<String>>(mut self, value: S) -> Self {
+        self.measurement = value.into();
+        self
+    }
+
+    /// Set the timeout for each request (default 10 seconds).
+    pub fn timeout<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.timeout = value.into();
+        self
+    }
+
+    /// Return the finished `InfluxWriter`.
+    pub fn build(self) -> InfluxWriter {
+        InfluxWriter {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url,
+            token: self.token,
+            measurement: self.measurement,
+        }
+    }
+}
+
+#[test]
+fn test_line_protocol() {
+    use std::time::Duration;
+    use crate::Neighbor;
+
+    let discovered = DiscoveredNeighbor {
+        neighbor: Neighbor::builder()
+            .mac_address([0, 0x0c, 0x42, 0, 0, 1])
+            .identity("core router, 1")
+            .board("")
+            .version("7.12 \"stable\"")
+            .uptime(Duration::from_secs(3600))
+            .build(),
+        first_seen: UNIX_EPOCH + Duration::from_secs(100),
+        last_seen: UNIX_EPOCH + Duration::from_secs(200),
+        vlan: None,
+        scope_id: None,
+    };
+    assert_eq!(
+        discovered.to_line_protocol(INFLUX_MEASUREMENT),
+        concat!(
+            r#"mndp_neighbor,mac=00:0C:42:00:00:01,identity=core\ router\,\ 1 "#,
+            r#"uptime=3600i,version="7.12 \"stable\"",first_seen=100i,last_seen=200i 200000000000"#,
+        ),
+    );
+}
@@ -0,0 +1,445 @@
+//! Kafka sink which publishes discovery events as JSON to a topic, using the
+//! `rskafka` client.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::iter;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rskafka::chrono::DateTime;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{Client, ClientBuilder, Credentials, SaslConfig};
+use rskafka::record::Record;
+use rskafka::BackoffConfig;
+
+use crate::DiscoveryEvent;
+
+// Most queued events published at once
+const MAX_BATCH: usize = 100;
+
+// Failing to publish; the events are dropped whatever the error
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// SASL mechanism used to authenticate to the brokers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KafkaSaslMechanism {
+    /// PLAIN; only safe over TLS.
+    Plain,
+    /// SCRAM-SHA-256.
+    ScramSha256,
+    /// SCRAM-SHA-512.
+    ScramSha512,
+}
+
+/// Compression of published record batches.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum KafkaCompression {
+    /// No compression.
+    #[default]
+    None,
+    /// gzip.
+    Gzip,
+    /// LZ4.
+    Lz4,
+    /// Snappy.
+    Snappy,
+    /// Zstandard.
+    Zstd,
+}
+
+/// Sink which publishes `DiscoveryEvent`s to a Kafka topic.
+///
+/// Each event is a record whose value is the event as JSON and whose key is
+/// the neighbor's MAC address, so all events for a device go to the same
+/// partition (chosen as the Java client's default partitioner does).
+/// Brokers may be reached over TLS and authenticated to with SASL, and
+/// records are acknowledged by all in-sync replicas. Events are queued and
+/// published in order from a background thread, so `notify` never blocks on
+/// the network. Failed requests are retried with exponential backoff for up
+/// to the timeout, after which their events are dropped. Dropping the
+/// `KafkaSink` waits for queued events to be published or given up on.
+#[derive(Debug)]
+pub struct KafkaSink {
+    sender: Option<Sender<DiscoveryEvent>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// TLS to the brokers, if any
+#[derive(Clone, Debug)]
+enum Tls {
+    None,
+    System,
+    Config(Arc<rustls::ClientConfig>),
+}
+
+// SASL credentials, kept out of `Debug` output
+#[derive(Clone)]
+struct Sasl {
+    mechanism: KafkaSaslMechanism,
+    username: String,
+    password: String,
+}
+
+impl fmt::Debug for Sasl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sasl").field("mechanism", &self.mechanism).field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+/// Builder structure for a `KafkaSink`.
+#[derive(Clone, Debug)]
+pub struct KafkaSinkBuilder {
+    brokers: Vec<String>,
+    topic: String,
+    client_id: String,
+    tls: Tls,
+    sasl: Option<Sasl>,
+    compression: KafkaCompression,
+    max_message_size: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    timeout: Duration,
+}
+
+impl KafkaSink {
+    /// Create a new Kafka sink builder which will publish to `topic`,
+    /// finding the cluster through `brokers`; e.g. `["kafka1:9092"]`.
+    pub fn builder<I, S, T>(brokers: I, topic: T) -> KafkaSinkBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        KafkaSinkBuilder {
+            brokers: brokers.into_iter().map(Into::into).collect(),
+            topic: topic.into(),
+            client_id: "mndp".to_string(),
+            tls: Tls::None,
+            sasl: None,
+            compression: KafkaCompression::None,
+            max_message_size: 1_000_000,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Queue an event for publishing.
+    pub fn notify(&self, event: &DiscoveryEvent) {
+        if let Some(sender) = &self.sender {
+            // The worker only exits once the sender is dropped
+            let _ = sender.send(event.clone());
+        }
+    }
+}
+
+impl Drop for KafkaSink {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl KafkaSinkBuilder {
+    /// Set the client ID sent to the brokers, which appears in their logs
+    /// and quotas (default 'mndp').
+    pub fn client_id<S: Into<String>>(mut self, value: S) -> Self {
+        self.client_id = value.into();
+        self
+    }
+
+    /// Connect to the brokers over TLS, verifying them against the system's
+    /// trusted certificates (default `false`).
+    pub fn tls(mut self, value: bool) -> Self {
+        self.tls = if value { Tls::System } else { Tls::None };
+        self
+    }
+
+    /// Connect to the brokers over TLS with this configuration; e.g. to
+    /// trust a private CA or present a client certificate.
+    pub fn tls_config(mut self, value: Arc<rustls::ClientConfig>) -> Self {
+        self.tls = Tls::Config(value);
+        self
+    }
+
+    /// Authenticate to the brokers with SASL (default none).
+    pub fn sasl<U: Into<String>, P: Into<String>>(mut self, mechanism: KafkaSaslMechanism, username: U, password: P) -> Self {
+        self.sasl = Some(Sasl { mechanism, username: username.into(), password: password.into() });
+        self
+    }
+
+    /// Set the compression of published record batches (default none).
+    pub fn compression(mut self, value: KafkaCompression) -> Self {
+        self.compression = value;
+        self
+    }
+
+    /// Set the largest record batch sent to a partition at once, before
+    /// compression; larger batches are split. Keep it within the topic's
+    /// `max.message.bytes` (default 1,000,000 bytes, within Kafka's default).
+    pub fn max_message_size(mut self, value: usize) -> Self {
+        self.max_message_size = value;
+        self
+    }
+
+    /// Set the delay before the first retry, increased on each further
+    /// retry (default 1 second).
+    pub fn backoff<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.backoff = value.into();
+        self
+    }
+
+    /// Set the upper limit on the delay between retries (default 60 seconds).
+    pub fn max_backoff<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.max_backoff = value.into();
+        self
+    }
+
+    /// Set how long to keep retrying a request before dropping its events
+    /// (default 30 seconds).
+    pub fn timeout<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.timeout = value.into();
+        self
+    }
+
+    /// Start the publishing thread and return the finished `KafkaSink`.
+    pub fn build(self) -> KafkaSink {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || self.run(receiver));
+        KafkaSink {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    fn run(self, receiver: Receiver<DiscoveryEvent>) {
+        let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_) => return,
+        };
+        let mut producer = Producer {
+            config: &self,
+            client: None,
+            partitions: Vec::new(),
+            clients: HashMap::new(),
+            next_partition: 0,
+        };
+
+        // Publish whatever has queued up behind each event together
+        while let Ok(event) = receiver.recv() {
+            let records: Vec<(Option<String>, Record)> = iter::once(event)
+                .chain(receiver.try_iter().take(MAX_BATCH - 1))
+                .filter_map(|event| record(&event))
+                .collect();
+            // The client has retried for the timeout; start again from the
+            // brokers with the next events
+            if runtime.block_on(producer.publish(records)).is_err() {
+                producer.reset();
+            }
+        }
+    }
+
+    fn client_builder(&self) -> Result<ClientBuilder, Error> {
+        let mut builder = ClientBuilder::new(self.brokers.clone())
+            .client_id(self.client_id.as_str())
+            .backoff_config(BackoffConfig {
+                init_backoff: self.backoff,
+                max_backoff: self.max_backoff,
+                base: 2.0,
+                deadline: Some(self.timeout),
+            });
+        match &self.tls {
+            Tls::None => {}
+            Tls::System => builder = builder.tls_config(system_tls()?),
+            Tls::Config(config) => builder = builder.tls_config(config.clone()),
+        }
+        if let Some(sasl) = &self.sasl {
+            let credentials = Credentials::new(sasl.username.clone(), sasl.password.clone());
+            builder = builder.sasl_config(match sasl.mechanism {
+                KafkaSaslMechanism::Plain => SaslConfig::Plain(credentials),
+                KafkaSaslMechanism::ScramSha256 => SaslConfig::ScramSha256(credentials),
+                KafkaSaslMechanism::ScramSha512 => SaslConfig::ScramSha512(credentials),
+            });
+        }
+        Ok(builder)
+    }
+}
+
+// TLS configuration trusting the system's certificates
+fn system_tls() -> Result<Arc<rustls::ClientConfig>, Error> {
+    let mut roots = rustls::RootCertStore::empty();
+    let certs = rustls_native_certs::load_native_certs();
+    let (added, _) = roots.add_parsable_certificates(certs.certs);
+    if added == 0 {
+        return Err("no trusted TLS certificates found".into());
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+// The event as a record, with the MAC address it is keyed by
+fn record(event: &DiscoveryEvent) -> Option<(Option<String>, Record)> {
+    let key = event.neighbor().neighbor.mac_address.map(|mac| mac.to_string());
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+    let record = Record {
+        key: key.clone().map(String::into_bytes),
+        value: Some(serde_json::to_vec(event).ok()?),
+        headers: BTreeMap::new(),
+        timestamp: DateTime::from_timestamp_millis(millis)?,
+    };
+    Some((key, record))
+}
+
+// Split records into batches of at most `max_size` bytes, in order; a
+// record larger than that is sent alone
+fn batches(records: Vec<Record>, max_size: usize) -> Vec<Vec<Record>> {
+    let mut batches: Vec<Vec<Record>> = Vec::new();
+    let mut size = 0;
+    for record in records {
+        let record_size = record.approximate_size();
+        match batches.last_mut() {
+            Some(batch) if size + record_size <= max_size => batch.push(record),
+            _ => {
+                batches.push(vec![record]);
+                size = 0;
+            }
+        }
+        size += record_size;
+    }
+    batches
+}
+
+// Client and partition clients, kept between batches until one fails
+struct Producer<'a> {
+    config: &'a KafkaSinkBuilder,
+    client: Option<Client>,
+    partitions: Vec<i32>,
+    clients: HashMap<i32, PartitionClient>,
+    next_partition: usize,
+}
+
+impl Producer<'_> {
+    fn reset(&mut self) {
+        self.client = None;
+        self.partitions.clear();
+        self.clients.clear();
+    }
+
+    async fn publish(&mut self, records: Vec<(Option<String>, Record)>) -> Result<(), Error> {
+        if self.client.is_none() {
+            let client = self.config.client_builder()?.build().await?;
+            let topics = client.list_topics().await?;
+            self.partitions = topics.into_iter()
+                .find(|topic| topic.name == self.config.topic)
+                .map(|topic| topic.partitions.into_iter().collect())
+                .unwrap_or_default();
+            if self.partitions.is_empty() {
+                return Err(format!("topic '{}' not found", self.config.topic).into());
+            }
+            self.client = Some(client);
+        }
+
+        let mut partitions: BTreeMap<i32, Vec<Record>> = BTreeMap::new();
+        for (key, record) in records {
+            // Records without a MAC address are spread over the partitions
+            let index = match &key {
+                Some(key) => (murmur2(key.as_bytes()) & 0x7fff_ffff) as usize,
+                None => {
+                    self.next_partition = self.next_partition.wrapping_add(1);
+                    self.next_partition
+                }
+            };
+            let partition = self.partitions[index % self.partitions.len()];
+            partitions.entry(partition).or_default().push(record);
+        }
+
+        let compression = match self.config.compression {
+            KafkaCompression::None => Compression::NoCompression,
+            KafkaCompression::Gzip => Compression::Gzip,
+            KafkaCompression::Lz4 => Compression::Lz4,
+            KafkaCompression::Snappy => Compression::Snappy,
+            KafkaCompression::Zstd => Compression::Zstd,
+        };
+        for (partition, records) in partitions {
+            if !self.clients.contains_key(&partition) {
+                let client = self.client.as_ref().unwrap();
+                let partition_client = client.partition_client(self.config.topic.as_str(), partition, UnknownTopicHandling::Retry).await?;
+                self.clients.insert(partition, partition_client);
+            }
+            for batch in batches(records, self.config.max_message_size) {
+                self.clients[&partition].produce(batch, compression).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The Java client's murmur2 hash, so keys map to the same partitions as
+// its default partitioner
+fn murmur2(data: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    let mut h = 0x9747_b28c ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (index, byte) in tail.iter().enumerate() {
+            h ^= u32::from(*byte) << (8 * index);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+#[test]
+fn test_kafka_murmur2() {
+    assert_eq!(murmur2(b"21") as i32, -973_932_308);
+    assert_eq!(murmur2(b"foobar") as i32, -790_332_482);
+    assert_eq!(murmur2(b"a-little-bit-long-string") as i32, -985_981_536);
+    assert_eq!(murmur2(b"abc") as i32, 479_470_107);
+}
+
+#[test]
+fn test_kafka_batches() {
+    use crate::{DiscoveredNeighbor, Neighbor};
+
+    let records: Vec<Record> = (0..5u8)
+        .map(|index| {
+            let event = DiscoveryEvent::Added(DiscoveredNeighbor {
+                neighbor: Neighbor::builder().mac_address([0, 0, 0, 0, 0, index]).identity("router1").build(),
+                first_seen: SystemTime::now(),
+                last_seen: SystemTime::now(),
+                vlan: None,
+                scope_id: None,
+            });
+            record(&event).unwrap().1
+        })
+        .collect();
+    let size = records[0].approximate_size();
+    assert_eq!(records[0].key.as_deref(), Some(&b"00:00:00:00:00:00"[..]));
+
+    // Batches are split in order, and a record too large for any still goes
+    let split = batches(records.clone(), size * 2);
+    assert_eq!(split.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+    assert_eq!(split.concat(), records);
+    assert_eq!(batches(records.clone(), 1).len(), 5);
+    assert_eq!(batches(records, usize::MAX).len(), 1);
+}
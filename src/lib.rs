@@ -4,12 +4,108 @@
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "discovery")]
+mod addressing;
+#[cfg(feature = "discovery")]
+mod announce;
+#[cfg(all(feature = "avahi", target_os = "linux"))]
+mod avahi;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+mod dbus;
+#[cfg(feature = "discovery")]
+mod dedup;
+#[cfg(feature = "discovery")]
+mod discovery;
+mod dot;
+mod event;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "sqlite")]
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod influx;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "discovery")]
+mod limit;
+#[cfg(all(feature = "discovery", target_os = "linux"))]
+mod mmsg;
 mod neighbor;
+#[cfg(feature = "discovery")]
+mod mock;
+#[cfg(feature = "netbox")]
+mod netbox;
+#[cfg(all(feature = "discovery", target_os = "linux"))]
+mod netns;
+#[cfg(feature = "mio")]
+mod poll;
 mod protocol;
+#[cfg(all(feature = "raw", target_os = "linux"))]
+mod raw;
+mod sanitize;
+mod set;
+#[cfg(feature = "discovery")]
+mod simulator;
+#[cfg(feature = "discovery")]
+mod spoof;
+mod table;
+#[cfg(all(test, any(feature = "webhook", feature = "netbox")))]
+mod test_server;
+mod timestamp;
+#[cfg(feature = "serde")]
+mod serde_util;
+#[cfg(feature = "webhook")]
+mod webhook;
 
 // pub extern crate bytes;
 pub extern crate macaddr;
 
-pub use crate::neighbor::{Neighbor, Builder, Unpack};
-pub use crate::protocol::{Packet, MndpType, TypeValue, SOLICIT};
+#[cfg(feature = "discovery")]
+pub use crate::addressing::{interface_broadcasts, interface_index, interface_name, ipv4_destination, ipv4_subnet_broadcast, ipv6_destination, local_interfaces};
+#[cfg(feature = "discovery")]
+pub use crate::announce::{Announcer, AnnouncerBuilder, ANNOUNCE_INTERVAL};
+#[cfg(all(feature = "avahi", target_os = "linux"))]
+pub use crate::avahi::{AvahiPublisher, AVAHI_SERVICE_TYPE};
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+pub use crate::dbus::{Bus, DbusService, DBUS_NAME, DBUS_PATH};
+#[cfg(feature = "discovery")]
+pub use crate::discovery::{discover, AddressFamily, Clock, Discovery, DiscoveryBuilder, SystemClock, Transport, UdpTransport, MNDP_PORT, MNDP_IPV6_GROUP};
+#[cfg(feature = "async")]
+pub use crate::discovery::discover_async;
+pub use crate::dot::topology_dot;
+pub use crate::event::{DiscoveryEvent, Suspicion};
+#[cfg(feature = "grpc")]
+pub use crate::grpc::{proto, GrpcService};
+#[cfg(feature = "sqlite")]
+pub use crate::history::{History, HistoryError, Sighting};
+#[cfg(feature = "http")]
+pub use crate::http::HttpServer;
+pub use crate::influx::INFLUX_MEASUREMENT;
+#[cfg(feature = "influx")]
+pub use crate::influx::{InfluxWriter, InfluxWriterBuilder};
+#[cfg(feature = "kafka")]
+pub use crate::kafka::{KafkaCompression, KafkaSaslMechanism, KafkaSink, KafkaSinkBuilder};
+#[cfg(feature = "discovery")]
+pub use crate::limit::RateLimiter;
+pub use crate::neighbor::{Neighbor, NeighborField, Builder, ParseError, Unpack};
+#[cfg(feature = "discovery")]
+pub use crate::mock::{MockClock, MockTransport};
+#[cfg(feature = "netbox")]
+pub use crate::netbox::{NetBox, NetBoxBuilder, NetBoxError};
+#[cfg(all(feature = "discovery", target_os = "linux"))]
+pub use crate::netns::in_namespace;
+#[cfg(feature = "mio")]
+pub use crate::poll::{MioTransport, PollDiscovery, PollDiscoveryBuilder};
+pub use crate::protocol::{Packet, PacketRef, NeighborRef, FieldRef, MndpType, TypeValue, FieldError, DEFAULT_MAX_PACKET_SIZE, SOLICIT};
+#[cfg(all(feature = "raw", target_os = "linux"))]
+pub use crate::raw::RawTransport;
+pub use crate::sanitize::sanitize;
+pub use crate::set::NeighborSet;
+#[cfg(feature = "discovery")]
+pub use crate::simulator::{Simulator, SimulatorBuilder};
+pub use crate::table::{NeighborTable, DiscoveredNeighbor, Received, DEFAULT_TTL};
+pub use crate::timestamp::{format_rfc3339, parse_rfc3339};
+#[cfg(feature = "webhook")]
+pub use crate::webhook::{Webhook, WebhookBuilder};
 
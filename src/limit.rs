@@ -0,0 +1,93 @@
+//! Token-bucket rate limiting of sent packets.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token-bucket rate limiter: up to `burst` packets may be sent at once,
+/// after which one more is allowed per `interval`. Used by `Announcer` to
+/// bound its announcements, so floods of solicitations cannot make it
+/// amplify traffic.
+#[derive(Debug)]
+pub struct RateLimiter {
+    burst: u32,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Create a full bucket of `burst` tokens, refilled by one per
+    /// `interval`.
+    pub fn new(burst: u32, interval: Duration) -> RateLimiter {
+        RateLimiter {
+            burst,
+            interval,
+            state: Mutex::new(State { tokens: burst.into(), updated: Instant::now() }),
+        }
+    }
+
+    /// Maximum number of packets sent at once.
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Time in which one token is refilled.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Take a token if one is available, returning whether a packet may be
+    /// sent now.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.updated);
+        let refilled = match self.interval.is_zero() {
+            true => f64::INFINITY,
+            false => elapsed.as_secs_f64() / self.interval.as_secs_f64(),
+        };
+        state.tokens = (state.tokens + refilled).min(self.burst.into());
+        state.updated = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    /// Bursts of 10 packets, then one per second.
+    fn default() -> RateLimiter {
+        RateLimiter::new(10, Duration::from_secs(1))
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(2, Duration::from_secs(1));
+    let start = limiter.state.lock().unwrap().updated;
+
+    assert!(limiter.try_acquire_at(start));
+    assert!(limiter.try_acquire_at(start));
+    assert!(!limiter.try_acquire_at(start));
+
+    // Refilled at one token per interval, up to the burst size
+    assert!(!limiter.try_acquire_at(start + Duration::from_millis(500)));
+    assert!(limiter.try_acquire_at(start + Duration::from_secs(1)));
+    assert!(!limiter.try_acquire_at(start + Duration::from_secs(1)));
+    let later = start + Duration::from_secs(60);
+    assert!(limiter.try_acquire_at(later));
+    assert!(limiter.try_acquire_at(later));
+    assert!(!limiter.try_acquire_at(later));
+}
@@ -0,0 +1,75 @@
+//! Batched UDP receive with Linux `recvmmsg`, taking several datagrams per
+//! system call.
+
+use std::io;
+use std::mem::{size_of, zeroed};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use crate::Received;
+
+// Most datagrams taken by one call
+pub(crate) const RECV_BATCH: usize = 16;
+
+// Receive up to `RECV_BATCH` datagrams from `fd` into consecutive `size`-byte
+// slots of `buf`, waiting for the first as `recv` does (so honouring the
+// read timeout or non-blocking mode) but not for the rest
+pub(crate) fn recv_batch(fd: RawFd, buf: &mut [u8], size: usize, received: &mut Vec<Received>) -> io::Result<()> {
+    let count = (buf.len() / size.max(1)).min(RECV_BATCH);
+    if count == 0 {
+        return Ok(());
+    }
+
+    let mut addrs: [libc::sockaddr_storage; RECV_BATCH] = unsafe { zeroed() };
+    let mut iovecs: [libc::iovec; RECV_BATCH] = unsafe { zeroed() };
+    let mut headers: [libc::mmsghdr; RECV_BATCH] = unsafe { zeroed() };
+    for (index, slot) in buf.chunks_exact_mut(size).take(count).enumerate() {
+        iovecs[index] = libc::iovec {
+            iov_base: slot.as_mut_ptr() as *mut libc::c_void,
+            iov_len: size,
+        };
+        let header = &mut headers[index].msg_hdr;
+        header.msg_name = &mut addrs[index] as *mut libc::sockaddr_storage as *mut libc::c_void;
+        header.msg_namelen = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        header.msg_iov = &mut iovecs[index];
+        header.msg_iovlen = 1;
+    }
+
+    let len = unsafe { libc::recvmmsg(fd, headers.as_mut_ptr(), count as libc::c_uint, libc::MSG_WAITFORONE, ptr::null_mut()) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // UDP sockets only receive from their own family; the caller relies on
+    // datagrams matching slots, so stop rather than skip one otherwise
+    for (header, addr) in headers.iter().zip(addrs.iter()).take(len as usize) {
+        match socket_addr(addr) {
+            Some(source) => received.push(Received {
+                len: header.msg_len as usize,
+                source,
+                vlan: None,
+                scope_id: None,
+                link_source: None,
+            }),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+fn socket_addr(addr: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match libc::c_int::from(addr.ss_family) {
+        libc::AF_INET => {
+            let addr = unsafe { &*(addr as *const libc::sockaddr_storage as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+        }
+        libc::AF_INET6 => {
+            let addr = unsafe { &*(addr as *const libc::sockaddr_storage as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), addr.sin6_flowinfo, addr.sin6_scope_id).into())
+        }
+        _ => None,
+    }
+}
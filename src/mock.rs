@@ -0,0 +1,148 @@
+//! In-memory transport and clock for testing code built on the discovery
+//! service without sockets or waiting.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+
+use crate::discovery::POLL_INTERVAL;
+use crate::{Clock, Neighbor, Packet, Transport, MNDP_PORT};
+
+// Source of datagrams injected without one (TEST-NET-1)
+const MOCK_SOURCE: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 1), MNDP_PORT);
+
+/// Transport which receives datagrams injected by a test and records those
+/// broadcast, instead of using a socket.
+///
+/// Clones share the same queues, so a test can keep one clone while giving
+/// another to `DiscoveryBuilder::transport` or `AnnouncerBuilder::transport`.
+#[derive(Clone, Debug, Default)]
+pub struct MockTransport {
+    inner: Arc<MockInner>,
+}
+
+#[derive(Debug, Default)]
+struct MockInner {
+    inbound: Mutex<VecDeque<(Bytes, SocketAddr)>>,
+    available: Condvar,
+    outbound: Mutex<Vec<Bytes>>,
+}
+
+impl MockTransport {
+    /// Create a new transport with nothing to receive.
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    /// Queue a datagram to be received from `source`.
+    pub fn inject<B: Into<Bytes>>(&self, datagram: B, source: SocketAddr) {
+        self.inner.inbound.lock().unwrap().push_back((datagram.into(), source));
+        self.inner.available.notify_all();
+    }
+
+    /// Queue an announcement of `neighbor` to be received, from
+    /// 192.0.2.1:5678.
+    pub fn inject_neighbor(&self, neighbor: &Neighbor) {
+        self.inject(Packet::from_neighbor(neighbor).to_bytes::<Bytes>(), MOCK_SOURCE.into());
+    }
+
+    /// Datagrams broadcast since the last call, oldest first.
+    pub fn take_sent(&self) -> Vec<Bytes> {
+        self.inner.outbound.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl Transport for MockTransport {
+    // Waits briefly for a datagram, as a socket with a read timeout would
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let inbound = self.inner.inbound.lock().unwrap();
+        let (mut inbound, _) = self.inner.available
+            .wait_timeout_while(inbound, POLL_INTERVAL, |inbound| inbound.is_empty())
+            .unwrap();
+        match inbound.pop_front() {
+            Some((datagram, source)) => {
+                // Truncated to the buffer, like a UDP socket
+                let len = datagram.len().min(buf.len());
+                buf[..len].copy_from_slice(&datagram[..len]);
+                Ok((len, source))
+            }
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, "nothing injected")),
+        }
+    }
+
+    fn broadcast(&self, buf: &[u8]) -> io::Result<()> {
+        self.inner.outbound.lock().unwrap().push(Bytes::copy_from_slice(buf));
+        Ok(())
+    }
+}
+
+/// Clock which only moves when told to, for testing expiry.
+///
+/// Clones share the same time, so a test can keep one clone while giving
+/// another to `DiscoveryBuilder::clock`.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    /// Create a clock showing `start`.
+    pub fn new(start: SystemTime) -> MockClock {
+        MockClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Set the time shown.
+    pub fn set(&self, time: SystemTime) {
+        *self.now.lock().unwrap() = time;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[test]
+fn test_mock_discovery() {
+    use crate::{Discovery, DiscoveryEvent, DEFAULT_TTL, SOLICIT};
+
+    let transport = MockTransport::new();
+    let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+    let discovery = Discovery::builder()
+        .transport(transport.clone())
+        .clock(clock.clone())
+        .start()
+        .unwrap();
+    let events = discovery.subscribe();
+
+    discovery.solicit().unwrap();
+    assert_eq!(transport.take_sent(), vec![SOLICIT.to_bytes::<Bytes>()]);
+
+    let neighbor = Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).identity("router1").build();
+    transport.inject_neighbor(&neighbor);
+    match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+        DiscoveryEvent::Added(added) => {
+            assert_eq!(added.neighbor, neighbor);
+            assert_eq!(added.last_seen, clock.now());
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    // Expiry follows the mock clock rather than the system time
+    clock.advance(DEFAULT_TTL + Duration::from_secs(1));
+    match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+        DiscoveryEvent::Expired(expired) => assert_eq!(expired.neighbor, neighbor),
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert!(discovery.neighbors().is_empty());
+}
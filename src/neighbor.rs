@@ -1,10 +1,17 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::net::{Ipv6Addr, Ipv4Addr};
+use std::str::FromStr;
 use std::time::Duration;
 
 use macaddr::MacAddr6;
 
+use crate::sanitize;
+
 /// MNDP 'unpack' field describing packing (compression) type.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Unpack {
     /// No packing.
     No,
@@ -14,8 +21,75 @@ pub enum Unpack {
     // UncompressedAll // Protocol research needed
 }
 
+impl fmt::Display for Unpack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Unpack::No => "no",
+            Unpack::Simple => "simple",
+        })
+    }
+}
+
+/// Field of a `Neighbor`; e.g. as reported changed by an `Updated` event.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NeighborField {
+    /// `Neighbor::board`
+    Board,
+    /// `Neighbor::identity`
+    Identity,
+    /// `Neighbor::interface_name`
+    InterfaceName,
+    /// `Neighbor::ipv4_address`
+    Ipv4Address,
+    /// `Neighbor::ipv6_address`
+    Ipv6Address,
+    /// `Neighbor::mac_address`
+    MacAddress,
+    /// `Neighbor::platform`
+    Platform,
+    /// `Neighbor::software_id`
+    SoftwareId,
+    /// `Neighbor::unpack`
+    Unpack,
+    /// `Neighbor::uptime`
+    Uptime,
+    /// `Neighbor::version`
+    Version,
+}
+
+impl NeighborField {
+    /// Name of the field, as in the serialized `Neighbor`; e.g. 'software_id'.
+    pub fn name(self) -> &'static str {
+        use NeighborField::*;
+        match self {
+            Board => "board",
+            Identity => "identity",
+            InterfaceName => "interface_name",
+            Ipv4Address => "ipv4_address",
+            Ipv6Address => "ipv6_address",
+            MacAddress => "mac_address",
+            Platform => "platform",
+            SoftwareId => "software_id",
+            Unpack => "unpack",
+            Uptime => "uptime",
+            Version => "version",
+        }
+    }
+}
+
+impl fmt::Display for NeighborField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// High-level representation of an MNDP neighbor.
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
+#[allow(clippy::manual_non_exhaustive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Neighbor {
     /// Board type/hardware model; e.g. 'CRS226-24G-2S+'.
     pub board: Option<String>,
@@ -29,6 +103,7 @@ pub struct Neighbor {
     pub ipv6_address: Option<Ipv6Addr>,
     // pub ipv6_enabled: Option<bool>,
     /// MAC address of MNDP interface.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::mac"))]
     pub mac_address: Option<MacAddr6>,
     /// Platform or operating system; e.g. 'MikroTik'.
     pub platform: Option<String>,
@@ -37,13 +112,45 @@ pub struct Neighbor {
     /// Compression setting on neighbor (more research is needed on this field).
     pub unpack: Option<Unpack>,
     /// Current uptime of neighbor.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::secs"))]
     pub uptime: Option<Duration>,
     /// Software version; e.g. '6.47.9 (long-term)'.
     pub version: Option<String>,
     // Private member to prevent assignment of entire structure.
+    #[cfg_attr(feature = "serde", serde(skip))]
     _private: ()
 }
 
+/// Displays one `name: value` line per field that is present, with control
+/// characters in strings escaped (see `sanitize`).
+impl fmt::Display for Neighbor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn line<T: fmt::Display>(f: &mut fmt::Formatter, name: &str, value: &Option<T>) -> fmt::Result {
+            match value {
+                Some(value) => writeln!(f, "{:>15}: {}", name, value),
+                None => Ok(()),
+            }
+        }
+
+        // Strings from the network are sanitized
+        fn text(value: &Option<String>) -> Option<Cow<'_, str>> {
+            value.as_deref().map(sanitize)
+        }
+
+        line(f, "mac-address", &self.mac_address)?;
+        line(f, "identity", &text(&self.identity))?;
+        line(f, "platform", &text(&self.platform))?;
+        line(f, "version", &text(&self.version))?;
+        line(f, "board", &text(&self.board))?;
+        line(f, "software-id", &text(&self.software_id))?;
+        line(f, "interface-name", &text(&self.interface_name))?;
+        line(f, "ipv4-address", &self.ipv4_address)?;
+        line(f, "ipv6-address", &self.ipv6_address)?;
+        line(f, "uptime", &self.uptime_human())?;
+        line(f, "unpack", &self.unpack)
+    }
+}
+
 impl Neighbor {
     /// Create a new blank Neighbor.
     pub fn new() -> Neighbor {
@@ -55,6 +162,103 @@ impl Neighbor {
         Builder::new()
     }
 
+    /// Uptime in days, hours and minutes; e.g. '13d 4h 22m'. Leading zero
+    /// units are left out, and an uptime under a minute is shown in seconds.
+    pub fn uptime_human(&self) -> Option<String> {
+        let secs = self.uptime?.as_secs();
+        let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+        Some(match (days, hours, minutes) {
+            (0, 0, 0) => format!("{}s", secs),
+            (0, 0, _) => format!("{}m", minutes),
+            (0, _, _) => format!("{}h {}m", hours, minutes),
+            _ => format!("{}d {}h {}m", days, hours, minutes),
+        })
+    }
+
+    /// Serialize as a JSON object.
+    #[cfg(feature = "json")]
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserialize from a JSON object; missing fields are `None`.
+    #[cfg(feature = "json")]
+    pub fn from_json_str(json: &str) -> serde_json::Result<Neighbor> {
+        serde_json::from_str(json)
+    }
+
+    /// Fields which differ between `self` and `other`, in `NeighborField`
+    /// order.
+    pub fn changed_fields(&self, other: &Neighbor) -> Vec<NeighborField> {
+        let fields = [
+            (NeighborField::Board, self.board != other.board),
+            (NeighborField::Identity, self.identity != other.identity),
+            (NeighborField::InterfaceName, self.interface_name != other.interface_name),
+            (NeighborField::Ipv4Address, self.ipv4_address != other.ipv4_address),
+            (NeighborField::Ipv6Address, self.ipv6_address != other.ipv6_address),
+            (NeighborField::MacAddress, self.mac_address != other.mac_address),
+            (NeighborField::Platform, self.platform != other.platform),
+            (NeighborField::SoftwareId, self.software_id != other.software_id),
+            (NeighborField::Unpack, self.unpack != other.unpack),
+            (NeighborField::Uptime, self.uptime != other.uptime),
+            (NeighborField::Version, self.version != other.version),
+        ];
+        fields.iter().filter(|(_, changed)| *changed).map(|(field, _)| *field).collect()
+    }
+
+    /// Merge in the fields present in `other`, which take precedence over
+    /// the fields of `self`.
+    pub fn merge(&mut self, other: &Neighbor) {
+        fn field<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                value.clone_from(other);
+            }
+        }
+
+        field(&mut self.board, &other.board);
+        field(&mut self.identity, &other.identity);
+        field(&mut self.interface_name, &other.interface_name);
+        field(&mut self.ipv4_address, &other.ipv4_address);
+        field(&mut self.ipv6_address, &other.ipv6_address);
+        field(&mut self.mac_address, &other.mac_address);
+        field(&mut self.platform, &other.platform);
+        field(&mut self.software_id, &other.software_id);
+        field(&mut self.unpack, &other.unpack);
+        field(&mut self.uptime, &other.uptime);
+        field(&mut self.version, &other.version);
+    }
+}
+
+/// Error from a string-based `Builder` setter; e.g. `mac_address_str`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    field: NeighborField,
+    value: String,
+}
+
+impl ParseError {
+    /// Field the value was given for.
+    pub fn field(&self) -> NeighborField {
+        self.field
+    }
+
+    /// Value which could not be parsed.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} '{}'", self.field, self.value)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Parse a setter value, reporting the field on failure
+fn parse<T: FromStr>(field: NeighborField, value: &str) -> Result<T, ParseError> {
+    value.trim().parse().map_err(|_| ParseError { field, value: value.to_string() })
 }
 
 /// Builder structure for a `Neighbor`.
@@ -113,6 +317,24 @@ impl Builder {
         self
     }
 
+    /// Set the MAC address for this instance from a string; e.g.
+    /// '00:0C:42:B8:A9:F3'.
+    pub fn mac_address_str(self, value: &str) -> Result<Self, ParseError> {
+        Ok(self.mac_address(parse::<MacAddr6>(NeighborField::MacAddress, value)?))
+    }
+
+    /// Set the IPv4 address for this instance from a string; e.g.
+    /// '192.168.88.1'.
+    pub fn ipv4_address_str(self, value: &str) -> Result<Self, ParseError> {
+        Ok(self.ipv4_address(parse::<Ipv4Addr>(NeighborField::Ipv4Address, value)?))
+    }
+
+    /// Set the IPv6 address for this instance from a string; e.g.
+    /// 'fe80::20c:42ff:feb8:a9f3'.
+    pub fn ipv6_address_str(self, value: &str) -> Result<Self, ParseError> {
+        Ok(self.ipv6_address(parse::<Ipv6Addr>(NeighborField::Ipv6Address, value)?))
+    }
+
     /// Set the platform name for this instance.
     pub fn platform<S: Into<String>>(mut self, value: S) -> Self {
         self.inner.platform = Some(value.into());
@@ -148,3 +370,28 @@ impl Builder {
         self.inner
     }
 }
+
+#[test]
+fn test_builder_str_setters() {
+    let neighbor = Neighbor::builder()
+        .mac_address_str("00:0C:42:B8:A9:F3").unwrap()
+        .ipv4_address_str("192.168.88.1").unwrap()
+        .ipv6_address_str("fe80::1").unwrap()
+        .build();
+    assert_eq!(neighbor.mac_address, Some(MacAddr6::new(0x00, 0x0c, 0x42, 0xb8, 0xa9, 0xf3)));
+    assert_eq!(neighbor.ipv4_address, Some(Ipv4Addr::new(192, 168, 88, 1)));
+
+    let error = Neighbor::builder().ipv4_address_str("192.168.88").unwrap_err();
+    assert_eq!(error.field(), NeighborField::Ipv4Address);
+    assert_eq!(error.to_string(), "invalid ipv4_address '192.168.88'");
+}
+
+#[test]
+fn test_uptime_human() {
+    let uptime = |secs| Neighbor::builder().uptime(Duration::from_secs(secs)).build().uptime_human();
+    assert_eq!(uptime(45).as_deref(), Some("45s"));
+    assert_eq!(uptime(22 * 60 + 5).as_deref(), Some("22m"));
+    assert_eq!(uptime(3600).as_deref(), Some("1h 0m"));
+    assert_eq!(uptime(13 * 86400 + 4 * 3600 + 22 * 60).as_deref(), Some("13d 4h 22m"));
+    assert_eq!(Neighbor::new().uptime_human(), None);
+}
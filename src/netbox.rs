@@ -0,0 +1,277 @@
+//! NetBox sink which records discovered neighbors as devices via the REST API.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::DiscoveredNeighbor;
+
+// Interface the primary IP is assigned to when the neighbor names none
+const DEFAULT_INTERFACE: &str = "mndp";
+
+/// Client which creates or updates a NetBox device for each discovered
+/// neighbor, for automatic inventory population.
+///
+/// Devices are named after the neighbor's identity, with its software ID as
+/// the serial number, and are matched by serial number or else by name. The
+/// device type is the one whose model is the neighbor's board, falling back
+/// to `NetBoxBuilder::device_type`. An IPv4 address is assigned to the
+/// device interface named after the neighbor's interface (creating it if
+/// needed) and made the primary IP.
+#[derive(Debug)]
+pub struct NetBox {
+    agent: ureq::Agent,
+    url: String,
+    token: String,
+    site: Option<u64>,
+    role: Option<u64>,
+    device_type: Option<u64>,
+}
+
+/// Builder structure for a `NetBox` client.
+#[derive(Clone, Debug)]
+pub struct NetBoxBuilder {
+    url: String,
+    token: String,
+    site: Option<u64>,
+    role: Option<u64>,
+    device_type: Option<u64>,
+    timeout: Duration,
+}
+
+/// Error from synchronizing a neighbor with NetBox.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NetBoxError {
+    /// The neighbor has no identity to name the device after.
+    NoIdentity,
+    /// No device type has the neighbor's board (if any) as its model, and no
+    /// default device type was set.
+    UnknownDeviceType(Option<String>),
+    /// NetBox rejected a request; the status code and response body.
+    Status(u16, String),
+    /// A request failed or its response could not be understood.
+    Transport(String),
+}
+
+impl fmt::Display for NetBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetBoxError::NoIdentity => write!(f, "neighbor has no identity"),
+            NetBoxError::UnknownDeviceType(Some(board)) => write!(f, "no device type with model '{}'", board),
+            NetBoxError::UnknownDeviceType(None) => write!(f, "neighbor has no board and no default device type is set"),
+            NetBoxError::Status(code, body) => write!(f, "NetBox returned status {}: {}", code, body),
+            NetBoxError::Transport(e) => write!(f, "NetBox request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetBoxError {}
+
+impl NetBox {
+    /// Create a new client builder for the NetBox instance at `url` (e.g.
+    /// 'https://netbox.example.com'), authenticating with an API `token`.
+    pub fn builder<U: Into<String>, T: Into<String>>(url: U, token: T) -> NetBoxBuilder {
+        NetBoxBuilder {
+            url: url.into(),
+            token: token.into(),
+            site: None,
+            role: None,
+            device_type: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Create or update the device for a neighbor, returning its NetBox ID.
+    pub fn sync(&self, discovered: &DiscoveredNeighbor) -> Result<u64, NetBoxError> {
+        let neighbor = &discovered.neighbor;
+        let name = neighbor.identity.as_deref().ok_or(NetBoxError::NoIdentity)?;
+
+        let mut existing = None;
+        if let Some(serial) = &neighbor.software_id {
+            existing = self.find("dcim/devices/", &[("serial", serial)])?;
+        }
+        if existing.is_none() {
+            existing = self.find("dcim/devices/", &[("name", name)])?;
+        }
+
+        let mut device = json!({ "name": name });
+        if let Some(serial) = &neighbor.software_id {
+            device["serial"] = serial.as_str().into();
+        }
+        let mut device_type = self.device_type;
+        if let Some(board) = &neighbor.board {
+            device_type = self.find("dcim/device-types/", &[("model", board)])?.or(device_type);
+        }
+        match device_type {
+            Some(id) => device["device_type"] = id.into(),
+            // An existing device keeps its type
+            None if existing.is_none() => return Err(NetBoxError::UnknownDeviceType(neighbor.board.clone())),
+            None => {}
+        }
+
+        let id = match existing {
+            Some(id) => {
+                self.request("PATCH", &format!("dcim/devices/{}/", id), &[], Some(&device))?;
+                id
+            }
+            None => {
+                if let Some(site) = self.site {
+                    device["site"] = site.into();
+                }
+                if let Some(role) = self.role {
+                    device["role"] = role.into();
+                }
+                id_of(&self.request("POST", "dcim/devices/", &[], Some(&device))?)?
+            }
+        };
+
+        if let Some(addr) = neighbor.ipv4_address {
+            let interface = neighbor.interface_name.as_deref().unwrap_or(DEFAULT_INTERFACE);
+            self.set_primary_ip(id, interface, addr)?;
+        }
+        Ok(id)
+    }
+
+    // Assign `addr` to the named interface of a device and make it primary
+    fn set_primary_ip(&self, device: u64, interface: &str, addr: Ipv4Addr) -> Result<(), NetBoxError> {
+        let device_id = device.to_string();
+        let interface = match self.find("dcim/interfaces/", &[("device_id", &device_id), ("name", interface)])? {
+            Some(id) => id,
+            None => {
+                let body = json!({ "device": device, "name": interface, "type": "other" });
+                id_of(&self.request("POST", "dcim/interfaces/", &[], Some(&body))?)?
+            }
+        };
+
+        let mut assignment = json!({ "assigned_object_type": "dcim.interface", "assigned_object_id": interface });
+        let ip = match self.find("ipam/ip-addresses/", &[("address", &addr.to_string())])? {
+            Some(id) => {
+                self.request("PATCH", &format!("ipam/ip-addresses/{}/", id), &[], Some(&assignment))?;
+                id
+            }
+            None => {
+                // The subnet is not announced, so record a host address
+                assignment["address"] = format!("{}/32", addr).into();
+                id_of(&self.request("POST", "ipam/ip-addresses/", &[], Some(&assignment))?)?
+            }
+        };
+
+        let body = json!({ "primary_ip4": ip });
+        self.request("PATCH", &format!("dcim/devices/{}/", device), &[], Some(&body))?;
+        Ok(())
+    }
+
+    // ID of the first object of a list endpoint matching `query`
+    fn find(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<u64>, NetBoxError> {
+        let response = self.request("GET", path, query, None)?;
+        match response["results"].as_array() {
+            Some(results) => results.first().map(id_of).transpose(),
+            None => Err(NetBoxError::Transport(String::from("response has no results"))),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, query: &[(&str, &str)], body: Option<&Value>) -> Result<Value, NetBoxError> {
+        let mut request = self.agent.request(method, &format!("{}/api/{}", self.url, path))
+            .set("Authorization", &format!("Token {}", self.token))
+            .set("Accept", "application/json");
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+
+        let response = match body {
+            Some(body) => request.set("Content-Type", "application/json").send_string(&body.to_string()),
+            None => request.call(),
+        };
+        match response {
+            Ok(response) => serde_json::from_reader(response.into_reader())
+                .map_err(|e| NetBoxError::Transport(e.to_string())),
+            Err(ureq::Error::Status(code, response)) => {
+                Err(NetBoxError::Status(code, response.into_string().unwrap_or_default()))
+            }
+            Err(e) => Err(NetBoxError::Transport(e.to_string())),
+        }
+    }
+}
+
+fn id_of(object: &Value) -> Result<u64, NetBoxError> {
+    object["id"].as_u64().ok_or_else(|| NetBoxError::Transport(String::from("response has no object ID")))
+}
+
+impl NetBoxBuilder {
+    /// Set the ID of the site new devices are created in.
+    pub fn site(mut self, value: u64) -> Self {
+        self.site = Some(value);
+        self
+    }
+
+    /// Set the ID of the role new devices are created with.
+    pub fn role(mut self, value: u64) -> Self {
+        self.role = Some(value);
+        self
+    }
+
+    /// Set the ID of the device type used when none has the neighbor's
+    /// board as its model.
+    pub fn device_type(mut self, value: u64) -> Self {
+        self.device_type = Some(value);
+        self
+    }
+
+    /// Set the timeout for each request (default 10 seconds).
+    pub fn timeout<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.timeout = value.into();
+        self
+    }
+
+    /// Return the finished `NetBox` client.
+    pub fn build(self) -> NetBox {
+        NetBox {
+            agent: ureq::AgentBuilder::new().timeout(self.timeout).build(),
+            url: self.url.trim_end_matches('/').to_string(),
+            token: self.token,
+            site: self.site,
+            role: self.role,
+            device_type: self.device_type,
+        }
+    }
+}
+
+#[test]
+fn test_netbox_create_device() {
+    use std::time::SystemTime;
+    use crate::Neighbor;
+
+    // Find no device, find the device type, then create the device
+    let (url, server) = crate::test_server::serve(&[
+        ("200 OK", r#"{"results": []}"#),
+        ("200 OK", r#"{"results": []}"#),
+        ("200 OK", r#"{"results": [{"id": 7}]}"#),
+        ("200 OK", r#"{"id": 42}"#),
+    ]);
+
+    let netbox = NetBox::builder(url, "secret").site(1).role(2).build();
+    let id = netbox.sync(&DiscoveredNeighbor {
+        neighbor: Neighbor::builder()
+            .identity("router1")
+            .board("RB5009UG+S+")
+            .software_id("ABCD-1234")
+            .build(),
+        first_seen: SystemTime::now(),
+        last_seen: SystemTime::now(),
+        vlan: None,
+        scope_id: None,
+    });
+    assert_eq!(id, Ok(42));
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].0, "GET /api/dcim/devices/?serial=ABCD-1234 HTTP/1.1");
+    assert_eq!(requests[1].0, "GET /api/dcim/devices/?name=router1 HTTP/1.1");
+    assert_eq!(requests[2].0, "GET /api/dcim/device-types/?model=RB5009UG%2BS%2B HTTP/1.1");
+    assert_eq!(requests[3].0, "POST /api/dcim/devices/ HTTP/1.1");
+    let body: Value = serde_json::from_str(&requests[3].1).unwrap();
+    assert_eq!(body, json!({
+        "name": "router1", "serial": "ABCD-1234", "device_type": 7, "site": 1, "role": 2,
+    }));
+}
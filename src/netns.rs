@@ -0,0 +1,56 @@
+//! Opening sockets in another Linux network namespace.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::thread;
+
+/// Run `f` in the network namespace `namespace`, returning its result;
+/// e.g. to open transports there. Sockets stay in the namespace they were
+/// created in, so they can be used from any thread afterwards.
+///
+/// `namespace` is either a name created by `ip netns add`, found in
+/// `/run/netns`, or a path such as `/proc/<pid>/ns/net`. Entering a
+/// namespace requires `CAP_SYS_ADMIN`; without it, open sockets inside the
+/// namespace by other means and pass them to `UdpTransport::from_socket`.
+pub fn in_namespace<T, F>(namespace: &str, f: F) -> io::Result<T>
+where
+    T: Send,
+    F: FnOnce() -> io::Result<T> + Send,
+{
+    let path = namespace_path(namespace);
+    let file = File::open(&path)
+        .map_err(|e| io::Error::new(e.kind(), format!("network namespace {}: {}", path.display(), e)))?;
+
+    // Only the calling thread changes namespace, so use a thread of our own
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            f()
+        }).join().unwrap()
+    })
+}
+
+fn namespace_path(namespace: &str) -> PathBuf {
+    if namespace.contains('/') {
+        PathBuf::from(namespace)
+    } else {
+        PathBuf::from("/run/netns").join(namespace)
+    }
+}
+
+#[test]
+fn test_in_namespace() {
+    use std::net::UdpSocket;
+
+    assert_eq!(namespace_path("blue"), PathBuf::from("/run/netns/blue"));
+    assert!(in_namespace("/nonexistent/ns", || Ok(())).is_err());
+
+    // Entering our own namespace may be refused without privileges
+    if let Ok(socket) = in_namespace("/proc/self/ns/net", || UdpSocket::bind("127.0.0.1:0")) {
+        assert!(socket.local_addr().unwrap().is_ipv4());
+    }
+}
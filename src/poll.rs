@@ -0,0 +1,393 @@
+//! Single-threaded discovery multiplexing its sockets with `mio`, for
+//! event-driven applications which do not use an async runtime.
+
+use std::io;
+use std::net::SocketAddr;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use macaddr::MacAddr6;
+use mio::event::Source;
+use mio::{Events, Interest, Poll, Registry, Token};
+
+use crate::dedup::DuplicateFilter;
+use crate::discovery::{broadcast_all, decode, default_udp_transports, source_scope_id, RecvPool, POLL_INTERVAL};
+use crate::spoof::SequenceTracker;
+use crate::{AddressFamily, Clock, DiscoveredNeighbor, DiscoveryEvent, NeighborTable, Received, SystemClock, Transport, UdpTransport, DEFAULT_MAX_PACKET_SIZE, MNDP_PORT, SOLICIT};
+
+/// Non-blocking UDP transport which can be registered with a `mio::Poll`.
+/// Receiving returns an error of kind `WouldBlock` when nothing is pending.
+#[derive(Debug)]
+pub struct MioTransport {
+    socket: mio::net::UdpSocket,
+    destination: SocketAddr,
+    index: Option<u32>,
+}
+
+impl MioTransport {
+    /// Create a non-blocking transport from a UDP transport, keeping its
+    /// destination and interface.
+    pub fn new(transport: UdpTransport) -> io::Result<MioTransport> {
+        transport.socket.set_nonblocking(true)?;
+        Ok(MioTransport {
+            socket: mio::net::UdpSocket::from_std(transport.socket),
+            destination: transport.destination,
+            index: transport.index,
+        })
+    }
+
+    /// The underlying socket.
+    pub fn socket(&self) -> &mio::net::UdpSocket {
+        &self.socket
+    }
+}
+
+impl Transport for MioTransport {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> io::Result<Received> {
+        let (len, source) = self.recv_from(buf)?;
+        let scope_id = self.index.or_else(|| source_scope_id(&source));
+        Ok(Received { len, source, vlan: None, scope_id, link_source: None })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn recv_batch(&self, buf: &mut [u8], size: usize, received: &mut Vec<Received>) -> io::Result<()> {
+        let start = received.len();
+        crate::mmsg::recv_batch(self.socket.as_raw_fd(), buf, size, received)?;
+        for received in &mut received[start..] {
+            received.scope_id = self.index.or_else(|| source_scope_id(&received.source));
+        }
+        Ok(())
+    }
+
+    fn broadcast(&self, buf: &[u8]) -> io::Result<()> {
+        #[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "watchos", target_os = "visionos",
+            target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+        match self.destination {
+            SocketAddr::V4(destination) if self.index.is_none() && *destination.ip() == std::net::Ipv4Addr::BROADCAST => {
+                return crate::discovery::broadcast_subnets(|destination| self.socket.send_to(buf, destination), destination.port());
+            }
+            _ => {}
+        }
+        self.socket.send_to(buf, self.destination).map(|_| ())
+    }
+}
+
+impl Source for MioTransport {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.socket.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.socket.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.socket.deregister(registry)
+    }
+}
+
+/// Discovery service driven by the caller, which waits for datagrams on all
+/// its sockets at once with `poll`.
+///
+/// Unlike `Discovery` it starts no threads: each call to `poll` processes
+/// what has been received and expires neighbors which have not been heard
+/// from within the TTL, returning the resulting events.
+pub struct PollDiscovery {
+    poll: Poll,
+    events: Events,
+    transports: Vec<MioTransport>,
+    table: NeighborTable,
+    pool: RecvPool,
+    datagrams: Vec<(Bytes, Received)>,
+    max_packet_size: usize,
+    duplicates: DuplicateFilter,
+    sequences: Option<SequenceTracker>,
+    clock: Box<dyn Clock>,
+    next_expiry: Instant,
+    // Receive error held back from a poll which also returned events
+    error: Option<io::Error>,
+}
+
+/// Builder structure for a `PollDiscovery` service.
+pub struct PollDiscoveryBuilder {
+    port: u16,
+    family: AddressFamily,
+    interfaces: Vec<String>,
+    namespace: Option<String>,
+    table: NeighborTable,
+    transports: Vec<MioTransport>,
+    max_packet_size: usize,
+    duplicate_window: Duration,
+    detect_spoofing: bool,
+    clock: Box<dyn Clock>,
+}
+
+impl PollDiscovery {
+    /// Create a new polled discovery service builder.
+    pub fn builder() -> PollDiscoveryBuilder {
+        PollDiscoveryBuilder {
+            port: MNDP_PORT,
+            family: AddressFamily::Any,
+            interfaces: Vec::new(),
+            namespace: None,
+            table: NeighborTable::new(),
+            transports: Vec::new(),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            duplicate_window: Duration::ZERO,
+            detect_spoofing: false,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Wait until a datagram arrives or `timeout` passes (indefinitely if
+    /// `None`), then process all pending datagrams and expire stale
+    /// neighbors, returning the resulting events in order. The wait ends
+    /// early when neighbors are due to be checked for expiry, so the result
+    /// may be empty before the timeout.
+    ///
+    /// An error receiving from a socket other than there being nothing left
+    /// to read stops draining it. It is returned if there are no events,
+    /// otherwise by the next call, so events already applied to the table
+    /// are not lost.
+    pub fn poll(&mut self, timeout: Option<Duration>) -> io::Result<Vec<DiscoveryEvent>> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let until_expiry = self.next_expiry.saturating_duration_since(Instant::now());
+        let timeout = timeout.map_or(until_expiry, |timeout| timeout.min(until_expiry));
+        match self.poll.poll(&mut self.events, Some(timeout)) {
+            Err(e) if e.kind() != io::ErrorKind::Interrupted => return Err(e),
+            _ => {}
+        }
+
+        let mut events = Vec::new();
+        let mut error = None;
+        for event in self.events.iter() {
+            let transport = &self.transports[event.token().0];
+
+            // Readiness is edge-triggered, so drain the socket
+            loop {
+                let index = event.token().0;
+                let result = self.pool.recv_batch(transport, &mut self.datagrams);
+                let now = self.clock.now();
+                for (bytes, received) in self.datagrams.drain(..) {
+                    let (neighbor, sequence) = match decode(bytes, self.max_packet_size) {
+                        Some(decoded) => decoded,
+                        None => continue,
+                    };
+                    let duplicates = &mut self.duplicates;
+                    if neighbor.mac_address.is_some_and(|mac| duplicates.is_duplicate(mac, sequence, now)) {
+                        continue;
+                    }
+                    if let Some(sequences) = &mut self.sequences {
+                        events.extend(sequences.check(&self.table, &neighbor, sequence, &received, index, now));
+                    }
+                    events.extend(self.table.update_received(neighbor, &received, now));
+                }
+                match result {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if Instant::now() >= self.next_expiry {
+            let now = self.clock.now();
+            events.extend(self.table.expire(now));
+            self.duplicates.prune(now);
+            if let Some(sequences) = &mut self.sequences {
+                sequences.prune(self.table.ttl(), now);
+            }
+            self.next_expiry = Instant::now() + POLL_INTERVAL;
+        }
+
+        match error {
+            Some(e) if events.is_empty() => Err(e),
+            error => {
+                self.error = error;
+                Ok(events)
+            }
+        }
+    }
+
+    /// Send a solicitation on all transports, prompting neighbors to announce
+    /// themselves. Succeeds if it was sent on at least one transport.
+    pub fn solicit(&self) -> io::Result<()> {
+        let bytes: Bytes = SOLICIT.to_bytes();
+        broadcast_all(self.transports.iter().map(|transport| transport as &dyn Transport), &bytes)
+    }
+
+    /// Snapshot of all currently known neighbors.
+    pub fn neighbors(&self) -> Vec<DiscoveredNeighbor> {
+        self.table.iter().cloned().collect()
+    }
+
+    /// Look up a currently known neighbor by MAC address.
+    pub fn neighbor(&self, mac: &MacAddr6) -> Option<&DiscoveredNeighbor> {
+        self.table.get(mac)
+    }
+
+    /// The current neighbor table; e.g. for saving to disk.
+    pub fn table(&self) -> &NeighborTable {
+        &self.table
+    }
+}
+
+impl PollDiscoveryBuilder {
+    /// Set the UDP port to listen on (default `MNDP_PORT`).
+    pub fn port(mut self, value: u16) -> Self {
+        self.port = value;
+        self
+    }
+
+    /// Set the address families to listen on (default both).
+    pub fn address_family(mut self, value: AddressFamily) -> Self {
+        self.family = value;
+        self
+    }
+
+    /// Listen only on the named network interface; e.g. 'ether1'. May be
+    /// called more than once (default all interfaces).
+    pub fn interface<S: Into<String>>(mut self, value: S) -> Self {
+        self.interfaces.push(value.into());
+        self
+    }
+
+    /// Open the default transports in a Linux network namespace, given by
+    /// name or path (see `in_namespace`), instead of the current one.
+    #[cfg(target_os = "linux")]
+    pub fn namespace<S: Into<String>>(mut self, value: S) -> Self {
+        self.namespace = Some(value.into());
+        self
+    }
+
+    /// Set the starting neighbor table; e.g. one loaded from disk. Its TTL
+    /// is used for expiry.
+    pub fn table(mut self, value: NeighborTable) -> Self {
+        self.table = value;
+        self
+    }
+
+    /// Use a custom transport. If any are given, the default UDP transports
+    /// are not created.
+    pub fn transport(mut self, value: MioTransport) -> Self {
+        self.transports.push(value);
+        self
+    }
+
+    /// Set the largest packet accepted; larger datagrams are dropped
+    /// (default `DEFAULT_MAX_PACKET_SIZE`).
+    pub fn max_packet_size(mut self, value: usize) -> Self {
+        self.max_packet_size = value;
+        self
+    }
+
+    /// Treat announcements with the same MAC address and sequence number
+    /// received within `window` of each other as one (see
+    /// `DiscoveryBuilder::suppress_duplicates`; default zero, which keeps
+    /// them all).
+    pub fn suppress_duplicates(mut self, window: Duration) -> Self {
+        self.duplicate_window = window;
+        self
+    }
+
+    /// Report announcements which look spoofed or replayed as `Suspicious`
+    /// events (see `DiscoveryBuilder::detect_spoofing`; default false).
+    pub fn detect_spoofing(mut self, value: bool) -> Self {
+        self.detect_spoofing = value;
+        self
+    }
+
+    /// Set the clock neighbors are timestamped and expired by (see
+    /// `DiscoveryBuilder::clock`; default `SystemClock`).
+    pub fn clock<C: Clock + 'static>(mut self, value: C) -> Self {
+        self.clock = Box::new(value);
+        self
+    }
+
+    /// Open the transports and register them for polling, with the same
+    /// defaults and tolerance of a missing address family as
+    /// `DiscoveryBuilder::start`.
+    pub fn start(self) -> io::Result<PollDiscovery> {
+        let mut transports = self.transports;
+        if transports.is_empty() {
+            let mut udp_transports = Vec::new();
+            if self.interfaces.is_empty() {
+                udp_transports = default_udp_transports(self.port, self.family, None, self.namespace.as_deref())?;
+            }
+            for interface in &self.interfaces {
+                udp_transports.extend(default_udp_transports(self.port, self.family, Some(interface), self.namespace.as_deref())?);
+            }
+            transports = udp_transports.into_iter().map(MioTransport::new).collect::<io::Result<_>>()?;
+        }
+
+        let poll = Poll::new()?;
+        for (index, transport) in transports.iter_mut().enumerate() {
+            poll.registry().register(transport, Token(index), Interest::READABLE)?;
+        }
+
+        Ok(PollDiscovery {
+            poll,
+            events: Events::with_capacity(transports.len().max(1)),
+            transports,
+            table: self.table,
+            pool: RecvPool::new(self.max_packet_size),
+            datagrams: Vec::new(),
+            max_packet_size: self.max_packet_size,
+            duplicates: DuplicateFilter::new(self.duplicate_window),
+            sequences: self.detect_spoofing.then(SequenceTracker::new),
+            clock: self.clock,
+            next_expiry: Instant::now() + POLL_INTERVAL,
+            error: None,
+        })
+    }
+}
+
+#[test]
+fn test_poll_discovery_loopback() {
+    use std::net::UdpSocket;
+    use std::time::SystemTime;
+    use crate::{MockClock, Neighbor, Packet};
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let mut discovery = PollDiscovery::builder()
+        .transport(MioTransport::new(UdpTransport::new(socket, addr)).unwrap())
+        .clock(MockClock::new(time))
+        .start()
+        .unwrap();
+
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .identity("router1")
+        .build();
+    let bytes: Bytes = Packet::from_neighbor(&neighbor).to_bytes();
+    UdpSocket::bind("127.0.0.1:0").unwrap().send_to(&bytes, addr).unwrap();
+
+    // Polls may return early for expiry checks
+    let start = Instant::now();
+    let mut events = Vec::new();
+    while events.is_empty() && start.elapsed() < Duration::from_secs(5) {
+        events = discovery.poll(Some(Duration::from_secs(5))).unwrap();
+    }
+    match events.as_slice() {
+        [DiscoveryEvent::Added(added)] => {
+            assert_eq!(added.neighbor, neighbor);
+            assert_eq!(added.first_seen, time);
+        }
+        events => panic!("unexpected events {:?}", events),
+    }
+    assert_eq!(discovery.neighbors().len(), 1);
+}
@@ -1,20 +1,30 @@
 #![allow(unused_imports)]
 #![allow(dead_code)]
 
+use std::borrow::Cow;
 use std::convert::{TryInto, TryFrom};
+use std::fmt;
 use std::mem::size_of;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::time::Duration;
 
 use bytes::{Bytes, BytesMut, Buf, BufMut};
+use macaddr::MacAddr6;
 
 use crate::{Neighbor, Unpack};
 
+/// Solicitation packet (zero header, sequence and no fields) which prompts
+/// neighbors to send their announcements.
 pub const SOLICIT: Packet = Packet {
     header: 0,
     sequence: 0,
     fields: Vec::new()
 };
 
+/// Default maximum size of an MNDP packet: the UDP payload of a 9000-byte
+/// jumbo frame over IPv6. Real announcements are far smaller.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 9000 - 40 - 8;
+
 // MNDP type values
 const MNDP_MAC_ADDRESS: u16 = 1;
 const MNDP_IDENTITY: u16 = 5;
@@ -33,16 +43,27 @@ const MNDP_IPV4_ADDRESS: u16 = 17;
 #[cfg_attr(test, derive(strum::EnumIter))]
 #[repr(u16)]
 pub enum MndpType {
+    /// MAC address of the announcing interface.
     MacAddress = MNDP_MAC_ADDRESS,
+    /// Identity or hostname.
     Identity = MNDP_IDENTITY,
+    /// Software version.
     Version = MNDP_VERSION,
+    /// Platform or operating system.
     Platform = MNDP_PLATFORM,
+    /// Uptime in seconds (u32, little endian).
     Uptime = MNDP_UPTIME,
+    /// Software ID.
     SoftwareId = MNDP_SOFTWARE_ID,
+    /// Board type/hardware model.
     Board = MNDP_BOARD,
+    /// Compression (unpack) setting.
     Unpack = MNDP_UNPACK,
+    /// IPv6 address of the announcing interface.
     Ipv6Address = MNDP_IPV6_ADDRESS,
+    /// Name of the announcing interface.
     InterfaceName = MNDP_INTERFACE_NAME,
+    /// IPv4 address of the announcing interface.
     Ipv4Address = MNDP_IPV4_ADDRESS,
     // Important: All variants must implement TryFrom<u16> correctly, below.
 }
@@ -79,11 +100,134 @@ pub struct TypeValue {
     pub value: Bytes
 }
 
+/// Error from a typed `TypeValue` accessor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FieldError {
+    /// The value does not have the length required by the type.
+    Length {
+        /// Required length in bytes.
+        expected: usize,
+        /// Actual length in bytes.
+        actual: usize,
+    },
+    /// The value is not valid UTF-8.
+    Utf8(std::str::Utf8Error),
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldError::Length { expected, actual } => {
+                write!(f, "field is {} bytes long, expected {}", actual, expected)
+            }
+            FieldError::Utf8(e) => write!(f, "field is not valid UTF-8: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FieldError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl TypeValue {
     /// Create a new TLV field with default/empty contents.
     pub fn new() -> TypeValue {
         Default::default()
     }
+
+    /// The value as a UTF-8 string.
+    pub fn as_str(&self) -> Result<&str, FieldError> {
+        FieldRef::from(self).as_str()
+    }
+
+    /// The value as an IPv4 address (4 bytes).
+    pub fn as_ipv4(&self) -> Result<Ipv4Addr, FieldError> {
+        FieldRef::from(self).as_ipv4()
+    }
+
+    /// The value as an IPv6 address (16 bytes).
+    pub fn as_ipv6(&self) -> Result<Ipv6Addr, FieldError> {
+        FieldRef::from(self).as_ipv6()
+    }
+
+    /// The value as a MAC address (6 bytes).
+    pub fn as_mac(&self) -> Result<MacAddr6, FieldError> {
+        FieldRef::from(self).as_mac()
+    }
+
+    /// The value as a little endian `u32`; e.g. the uptime in seconds.
+    pub fn as_u32_le(&self) -> Result<u32, FieldError> {
+        FieldRef::from(self).as_u32_le()
+    }
+}
+
+/// TLV field borrowed from a packet's bytes; see `PacketRef`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FieldRef<'a> {
+    /// MNDP type
+    pub typ: u16,
+    /// Field bytes.
+    pub value: &'a [u8]
+}
+
+impl<'a> From<&'a TypeValue> for FieldRef<'a> {
+    fn from(tv: &'a TypeValue) -> FieldRef<'a> {
+        FieldRef { typ: tv.typ, value: &tv.value }
+    }
+}
+
+impl<'a> FieldRef<'a> {
+    // The value as a fixed-size array
+    fn as_array<const N: usize>(&self) -> Result<[u8; N], FieldError> {
+        <[u8; N]>::try_from(self.value)
+            .map_err(|_| FieldError::Length { expected: N, actual: self.value.len() })
+    }
+
+    /// The value as a UTF-8 string.
+    pub fn as_str(&self) -> Result<&'a str, FieldError> {
+        std::str::from_utf8(self.value).map_err(FieldError::Utf8)
+    }
+
+    /// The value as an IPv4 address (4 bytes).
+    pub fn as_ipv4(&self) -> Result<Ipv4Addr, FieldError> {
+        self.as_array::<4>().map(Ipv4Addr::from)
+    }
+
+    /// The value as an IPv6 address (16 bytes).
+    pub fn as_ipv6(&self) -> Result<Ipv6Addr, FieldError> {
+        self.as_array::<16>().map(Ipv6Addr::from)
+    }
+
+    /// The value as a MAC address (6 bytes).
+    pub fn as_mac(&self) -> Result<MacAddr6, FieldError> {
+        self.as_array::<6>().map(MacAddr6::from)
+    }
+
+    /// The value as a little endian `u32`; e.g. the uptime in seconds.
+    pub fn as_u32_le(&self) -> Result<u32, FieldError> {
+        self.as_array::<4>().map(u32::from_le_bytes)
+    }
+
+    // The value as text, with invalid UTF-8 replaced as `to_neighbor` does
+    fn text(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.value)
+    }
+
+    // The unpack setting, if the value is a known one
+    fn unpack(&self) -> Option<Unpack> {
+        match self.value.first() {
+            Some(0) => Some(Unpack::No),
+            Some(1) => Some(Unpack::Simple),
+            // ?? => Some(Unpack::UncompressedHeaders), // todo
+            // ?? => Some(Unpack::UncompressedAll), // todo
+            _ => None
+        }
+    }
 }
 
 /// MNDP packet struct with conversions to/from `Neighbor` and raw bytes.
@@ -114,8 +258,46 @@ impl Packet {
         Default::default()
     }
 
-    /// Produce raw bytes from a `Packet` in MNDP protocol format.
+    /// Packet header value.
+    pub fn header(&self) -> u16 {
+        self.header
+    }
+
+    /// Packet sequence number.
+    pub fn sequence(&self) -> u16 {
+        self.sequence
+    }
+
+    /// Set the packet sequence number.
+    pub fn set_sequence(&mut self, sequence: u16) {
+        self.sequence = sequence;
+    }
+
+    /// TLV fields in the order they appear in the packet.
+    pub fn fields(&self) -> &[TypeValue] {
+        &self.fields
+    }
+
+    /// First field of the given type, if any.
+    pub fn get(&self, typ: MndpType) -> Option<&TypeValue> {
+        self.get_all(typ).next()
+    }
+
+    /// All fields of the given type, in packet order.
+    pub fn get_all(&self, typ: MndpType) -> impl Iterator<Item = &TypeValue> {
+        let typ = typ as u16;
+        self.fields.iter().filter(move |tv| tv.typ == typ)
+    }
+
+    /// Produce raw bytes from a `Packet` in MNDP protocol format, limited to
+    /// `DEFAULT_MAX_PACKET_SIZE`.
     pub fn to_bytes<B: From<Bytes>>(&self) -> B {
+        self.to_bytes_with_limit(DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    /// Produce raw bytes from a `Packet` in MNDP protocol format, at most
+    /// `max_size` bytes long. Fields which would not fit are left out.
+    pub fn to_bytes_with_limit<B: From<Bytes>>(&self, max_size: usize) -> B {
 
         // Allocate a new Bytes buffer with a reasonable capacity
         // (Ethernet payload size minus IPv6 and UDP headers)
@@ -127,17 +309,21 @@ impl Packet {
 
         // Write each TLV
         for tv in &self.fields {
-            buf.put_u16(tv.typ);
-
             // A bit of an edge case but we should check that
             // the length will fit into a u16
             let len = if tv.value.len() >= 65535 {
                 65535
             } else {
-                tv.value.len().into()
+                tv.value.len()
             };
+
+            // Leave out a field which would exceed the size limit
+            if buf.len() + 4 + len > max_size {
+                continue;
+            }
             
             // This (usize -> u16) will not panic because we check length above
+            buf.put_u16(tv.typ);
             buf.put_u16(len.try_into().unwrap());
             buf.put(tv.value.slice(0..len));
         }
@@ -145,69 +331,68 @@ impl Packet {
         // Convert to immutable and return
         buf.freeze().into()
     }
+
     /// Create a new `Packet` instance by parsing raw bytes in MNDP format.
-    /// Returns an error if input is shorter than 4 bytes.
+    /// Returns an error if input is shorter than 4 bytes or longer than
+    /// `DEFAULT_MAX_PACKET_SIZE`.
+    #[allow(clippy::result_unit_err)]
     pub fn from_bytes<B: Into<Bytes>>(bytes: B) -> Result<Packet, ()> {
-        let mut buf: Bytes = bytes.into();
-
-        // Check that buf is minimum required length (2 byte header, 2 byte seq id)
-        if buf.len() < 4 {
-            return Err(());
-        }
-
-        // Create a new packet
-        let mut packet = Packet::new();
-
-        // Get the header and seq
-        packet.header = buf.get_u16();
-        packet.sequence = buf.get_u16();
-
-        // Eat the TLVs
-        while buf.remaining() >= 4 {
-            // Get the type and length
-            let typ = buf.get_u16();
-            let len = buf.get_u16();
-
-            // Get the data if enough bytes remain
-            if buf.remaining() >= len.into() {
-                let bytes = buf.split_to(len.into());
-                packet.fields.push(TypeValue {
-                    typ: typ,
-                    value: bytes
-                });
-            }
-        }
+        Packet::from_bytes_with_limit(bytes, DEFAULT_MAX_PACKET_SIZE)
+    }
 
-        Ok(packet)
+    /// Create a new `Packet` instance by parsing raw bytes in MNDP format.
+    /// Returns an error if input is shorter than 4 bytes or longer than
+    /// `max_size`.
+    #[allow(clippy::result_unit_err)]
+    pub fn from_bytes_with_limit<B: Into<Bytes>>(bytes: B, max_size: usize) -> Result<Packet, ()> {
+        let bytes: Bytes = bytes.into();
+        let view = PacketRef::from_bytes_with_limit(&bytes, max_size)?;
+
+        // Fields share the input buffer rather than being copied
+        Ok(Packet {
+            header: view.header(),
+            sequence: view.sequence(),
+            fields: view.fields()
+                .map(|field| TypeValue { typ: field.typ, value: bytes.slice_ref(field.value) })
+                .collect()
+        })
     }
 
     /// Create a new `Neighbor` from a `Packet`.
+    /// Fields with an unexpected length for their type are ignored.
     pub fn to_neighbor(&self) -> Neighbor {
-
-        // Todo: Do length checks for non-string types
-
         let mut neighbor = Neighbor::builder();
 
         for tv in &self.fields {
             if let Ok(typ) = tv.typ.try_into() {
+                let value = tv.value.as_ref();
                 neighbor = match typ {
-                    MndpType::Board => neighbor.board(String::from_utf8_lossy(&tv.value).to_string()),
-                    MndpType::Identity => neighbor.identity(String::from_utf8_lossy(&tv.value).to_string()),
-                    MndpType::InterfaceName => neighbor.interface_name(String::from_utf8_lossy(&tv.value).to_string()),
-                    MndpType::Ipv4Address => neighbor.ipv4_address::<[u8; 4]>(tv.value.as_ref().try_into().unwrap()),
-                    MndpType::Ipv6Address => neighbor.ipv6_address::<[u8; 16]>(tv.value.as_ref().try_into().unwrap()),
-                    MndpType::MacAddress => neighbor.mac_address::<[u8; 6]>(tv.value.as_ref().try_into().unwrap()),
-                    MndpType::Platform => neighbor.platform(String::from_utf8_lossy(&tv.value).to_string()),
-                    MndpType::SoftwareId => neighbor.software_id(String::from_utf8_lossy(&tv.value).to_string()),
-                    MndpType::Unpack => match tv.value[0] {
-                        0 => neighbor.unpack(Unpack::No),
-                        1 => neighbor.unpack(Unpack::Simple),
-                        // ?? => neighbor.unpack(Unpack::UncompressedHeaders), // todo
-                        // ?? => neighbor.unpack(Unpack::UncompressedAll), // todo
-                        _ => neighbor
+                    MndpType::Board => neighbor.board(String::from_utf8_lossy(value).to_string()),
+                    MndpType::Identity => neighbor.identity(String::from_utf8_lossy(value).to_string()),
+                    MndpType::InterfaceName => neighbor.interface_name(String::from_utf8_lossy(value).to_string()),
+                    MndpType::Ipv4Address => match tv.as_ipv4() {
+                        Ok(addr) => neighbor.ipv4_address(addr),
+                        Err(_) => neighbor
+                    },
+                    MndpType::Ipv6Address => match tv.as_ipv6() {
+                        Ok(addr) => neighbor.ipv6_address(addr),
+                        Err(_) => neighbor
+                    },
+                    MndpType::MacAddress => match tv.as_mac() {
+                        Ok(addr) => neighbor.mac_address(addr),
+                        Err(_) => neighbor
+                    },
+                    MndpType::Platform => neighbor.platform(String::from_utf8_lossy(value).to_string()),
+                    MndpType::SoftwareId => neighbor.software_id(String::from_utf8_lossy(value).to_string()),
+                    MndpType::Unpack => match FieldRef::from(tv).unpack() {
+                        Some(unpack) => neighbor.unpack(unpack),
+                        None => neighbor
                     },
-                    MndpType::Uptime => neighbor.uptime(Duration::from_secs(tv.value.as_ref().get_u32_le().into())),
-                    MndpType::Version => neighbor.version(String::from_utf8_lossy(&tv.value).to_string())
+                    MndpType::Uptime => match tv.as_u32_le() {
+                        Ok(secs) => neighbor.uptime(Duration::from_secs(secs.into())),
+                        Err(_) => neighbor
+                    },
+                    MndpType::Version => neighbor.version(String::from_utf8_lossy(value).to_string())
                 };
             }
         }
@@ -277,6 +462,176 @@ impl Packet {
 
 }
 
+/// MNDP packet borrowed from a buffer, whose fields are found and decoded
+/// only when accessed.
+///
+/// Parsing a `Packet` allocates its list of fields, and `to_neighbor` then
+/// copies every string; a `PacketRef` only checks the packet's length, so
+/// tooling which looks at a few fields of many packets (e.g. counting MAC
+/// addresses in a capture) avoids both. Fields are read as by
+/// `Packet::from_bytes`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketRef<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> PacketRef<'a> {
+    /// Borrow a packet in MNDP format. Returns an error if input is shorter
+    /// than 4 bytes or longer than `DEFAULT_MAX_PACKET_SIZE`.
+    #[allow(clippy::result_unit_err)]
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<PacketRef<'a>, ()> {
+        PacketRef::from_bytes_with_limit(bytes, DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    /// Borrow a packet in MNDP format. Returns an error if input is shorter
+    /// than 4 bytes or longer than `max_size`.
+    #[allow(clippy::result_unit_err)]
+    pub fn from_bytes_with_limit(bytes: &'a [u8], max_size: usize) -> Result<PacketRef<'a>, ()> {
+        // Minimum required length is a 2 byte header and 2 byte seq id
+        if bytes.len() < 4 || bytes.len() > max_size {
+            return Err(());
+        }
+        Ok(PacketRef { bytes })
+    }
+
+    /// Packet header value.
+    pub fn header(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[0], self.bytes[1]])
+    }
+
+    /// Packet sequence number.
+    pub fn sequence(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[2], self.bytes[3]])
+    }
+
+    /// TLV fields in the order they appear in the packet, read as the
+    /// iterator advances.
+    pub fn fields(&self) -> impl Iterator<Item = FieldRef<'a>> {
+        let mut buf = &self.bytes[4..];
+        std::iter::from_fn(move || {
+            while buf.remaining() >= 4 {
+                let typ = buf.get_u16();
+                let len = buf.get_u16() as usize;
+
+                // A field longer than the rest of the packet is skipped
+                if buf.remaining() >= len {
+                    let (value, rest) = buf.split_at(len);
+                    buf = rest;
+                    return Some(FieldRef { typ, value });
+                }
+            }
+            None
+        })
+    }
+
+    /// First field of the given type, if any.
+    pub fn get(&self, typ: MndpType) -> Option<FieldRef<'a>> {
+        self.get_all(typ).next()
+    }
+
+    /// All fields of the given type, in packet order.
+    pub fn get_all(&self, typ: MndpType) -> impl Iterator<Item = FieldRef<'a>> {
+        let typ = typ as u16;
+        self.fields().filter(move |field| field.typ == typ)
+    }
+
+    /// Lazily decoded view of the neighbor announced.
+    pub fn neighbor(&self) -> NeighborRef<'a> {
+        NeighborRef { packet: *self }
+    }
+
+    /// Copy into an owned `Packet`.
+    pub fn to_packet(&self) -> Packet {
+        Packet {
+            header: self.header(),
+            sequence: self.sequence(),
+            fields: self.fields()
+                .map(|field| TypeValue { typ: field.typ, value: Bytes::copy_from_slice(field.value) })
+                .collect()
+        }
+    }
+}
+
+/// View of the neighbor announced in a `PacketRef`, decoding each field
+/// when its accessor is called.
+///
+/// Each accessor returns what the same field of `Packet::to_neighbor` would
+/// be: the last field of its type which decodes, with strings converted
+/// lossily and borrowed from the packet unless they are invalid UTF-8.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NeighborRef<'a> {
+    packet: PacketRef<'a>
+}
+
+impl<'a> NeighborRef<'a> {
+    // Last field of the given type which decodes
+    fn last<T, F: Fn(FieldRef<'a>) -> Option<T>>(&self, typ: MndpType, decode: F) -> Option<T> {
+        self.packet.get_all(typ).filter_map(decode).last()
+    }
+
+    /// Board type/hardware model.
+    pub fn board(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::Board, |field| Some(field.text()))
+    }
+
+    /// Identity or hostname.
+    pub fn identity(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::Identity, |field| Some(field.text()))
+    }
+
+    /// Name of neighbor interface.
+    pub fn interface_name(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::InterfaceName, |field| Some(field.text()))
+    }
+
+    /// IPv4 address of neighbor interface.
+    pub fn ipv4_address(&self) -> Option<Ipv4Addr> {
+        self.last(MndpType::Ipv4Address, |field| field.as_ipv4().ok())
+    }
+
+    /// IPv6 address of neighbor interface.
+    pub fn ipv6_address(&self) -> Option<Ipv6Addr> {
+        self.last(MndpType::Ipv6Address, |field| field.as_ipv6().ok())
+    }
+
+    /// MAC address of MNDP interface.
+    pub fn mac_address(&self) -> Option<MacAddr6> {
+        self.last(MndpType::MacAddress, |field| field.as_mac().ok())
+    }
+
+    /// Platform or operating system.
+    pub fn platform(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::Platform, |field| Some(field.text()))
+    }
+
+    /// Software ID or unique identifier.
+    pub fn software_id(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::SoftwareId, |field| Some(field.text()))
+    }
+
+    /// Compression setting on neighbor.
+    pub fn unpack(&self) -> Option<Unpack> {
+        self.last(MndpType::Unpack, |field| field.unpack())
+    }
+
+    /// Current uptime of neighbor.
+    pub fn uptime(&self) -> Option<Duration> {
+        self.last(MndpType::Uptime, |field| field.as_u32_le().ok())
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+
+    /// Software version.
+    pub fn version(&self) -> Option<Cow<'a, str>> {
+        self.last(MndpType::Version, |field| Some(field.text()))
+    }
+
+    /// Decode every field into an owned `Neighbor`, as
+    /// `Packet::to_neighbor` does.
+    pub fn to_neighbor(&self) -> Neighbor {
+        self.packet.to_packet().to_neighbor()
+    }
+}
+
 #[test]
 fn test_packet_from_bytes() {
     let bytes: Bytes = hex::decode("3cc6000000010006c4ad34bf91110005000b656f622d726f75746572310007000f362e34382e312028737461626c6529000800084d696b726f54696b000a000441752e00000b0009324150372d5a564335000c00085242373630694753000e000101000f001026006c50067f7700000000000000000100100007766c616e31353700110004ac129d01").unwrap().into();
@@ -285,12 +640,93 @@ fn test_packet_from_bytes() {
     assert_eq!(bytes, res);
 }
 
+#[test]
+fn test_packet_size_limit() {
+    let neighbor = Neighbor::builder().identity("r1").board("x".repeat(100)).build();
+    let packet = Packet::from_neighbor(&neighbor);
+
+    // The board field does not fit, so is left out
+    let bytes: Bytes = packet.to_bytes_with_limit(64);
+    assert_eq!(hex::encode(&bytes), "00000000000500027231");
+
+    let bytes: Bytes = packet.to_bytes();
+    assert_eq!(bytes.len(), 4 + 104 + 6);
+    assert_eq!(Packet::from_bytes_with_limit(bytes.clone(), 100), Err(()));
+    assert_eq!(Packet::from_bytes(bytes).unwrap().to_neighbor(), neighbor);
+}
+
+#[test]
+fn test_packet_to_neighbor_bad_lengths() {
+    // MAC address, IPv4 address and uptime one byte short, empty unpack field
+    let bytes = hex::decode("0000000000010005000000000000110003000000000a0003000000000e0000000500027231").unwrap();
+    let neighbor = Packet::from_bytes(bytes).unwrap().to_neighbor();
+    assert_eq!(neighbor, Neighbor::builder().identity("r1").build());
+}
+
+#[test]
+fn test_packet_get() {
+    let bytes = hex::decode("0000000000050002723100010006000000000001000500027232").unwrap();
+    let packet = Packet::from_bytes(bytes).unwrap();
+    assert_eq!(packet.get(MndpType::Identity).unwrap().value.as_ref(), b"r1");
+    assert_eq!(packet.get_all(MndpType::Identity).count(), 2);
+    assert_eq!(packet.get(MndpType::MacAddress).unwrap().value.len(), 6);
+    assert_eq!(packet.get(MndpType::Board), None);
+}
+
+#[test]
+fn test_packet_ref() {
+    // Announcement, then the bad lengths and duplicate identity above,
+    // invalid UTF-8 and a field longer than the rest of the packet
+    let bytes = hex::decode(concat!(
+        "3cc6000000010006c4ad34bf91110005000b656f622d726f75746572310007000f362e34382e312028737461626c6529000800084d696b726f54696b000a000441752e00000b0009324150372d5a564335000c00085242373630694753000e000101000f001026006c50067f7700000000000000000100100007766c616e31353700110004ac129d01",
+        "00010005000000000000110003000000000a0003000000000e0000000500027232",
+        "00080002ff41",
+        "000c0010ff",
+    )).unwrap();
+    let packet = Packet::from_bytes(bytes.clone()).unwrap();
+    let view = PacketRef::from_bytes(&bytes).unwrap();
+
+    assert_eq!(view.to_packet(), packet);
+    assert_eq!((view.header(), view.sequence()), (packet.header(), packet.sequence()));
+    assert_eq!(view.get(MndpType::Identity).unwrap().as_str(), Ok("eob-router1"));
+
+    let neighbor = packet.to_neighbor();
+    let lazy = view.neighbor();
+    assert_eq!(lazy.to_neighbor(), neighbor);
+    assert_eq!(lazy.identity().as_deref(), Some("r2"));
+    assert!(matches!(lazy.identity(), Some(Cow::Borrowed(_))));
+    assert_eq!(lazy.mac_address(), neighbor.mac_address);
+    assert_eq!(lazy.ipv4_address(), neighbor.ipv4_address);
+    assert_eq!(lazy.ipv6_address(), neighbor.ipv6_address);
+    assert_eq!(lazy.uptime(), neighbor.uptime);
+    assert_eq!(lazy.unpack(), neighbor.unpack);
+    assert_eq!(lazy.board(), neighbor.board.map(Cow::from));
+    assert_eq!(lazy.platform().as_deref(), Some("\u{fffd}A"));
+
+    assert_eq!(PacketRef::from_bytes(&bytes[..3]), Err(()));
+    assert_eq!(PacketRef::from_bytes_with_limit(&bytes, 100), Err(()));
+}
+
+#[test]
+fn test_type_value_accessors() {
+    let tv = |value: &[u8]| TypeValue { typ: 0, value: Bytes::copy_from_slice(value) };
+
+    assert_eq!(tv(b"r1").as_str(), Ok("r1"));
+    assert!(matches!(tv(&[0xff]).as_str(), Err(FieldError::Utf8(_))));
+    assert_eq!(tv(&[192, 168, 88, 1]).as_ipv4(), Ok(Ipv4Addr::new(192, 168, 88, 1)));
+    assert_eq!(tv(&[192, 168, 88]).as_ipv4(), Err(FieldError::Length { expected: 4, actual: 3 }));
+    assert_eq!(tv(&[0; 16]).as_ipv6(), Ok(Ipv6Addr::UNSPECIFIED));
+    assert_eq!(tv(&[0, 0, 0, 0, 0, 1]).as_mac(), Ok(MacAddr6::new(0, 0, 0, 0, 0, 1)));
+    assert_eq!(tv(&[0x41, 0x75, 0x2e, 0]).as_u32_le(), Ok(3044673));
+    assert_eq!(tv(&[]).as_u32_le(), Err(FieldError::Length { expected: 4, actual: 0 }));
+}
+
 #[test]
 fn test_mndp_type_try_into() {
     use strum::IntoEnumIterator;
     for mndp_type in MndpType::iter() {
         let a = mndp_type as u16;
-        let b: MndpType = a.try_into().expect(format!("TryInto<u16> not implemented for {:?}", mndp_type).as_str());
+        let b: MndpType = a.try_into().unwrap_or_else(|_| panic!("TryInto<u16> not implemented for {:?}", mndp_type));
         assert_eq!(mndp_type, b);
     }
 }
@@ -0,0 +1,395 @@
+//! Link-layer capture transport using a Linux `AF_PACKET` socket, which sees
+//! the 802.1Q VLAN tags of the frames carrying MNDP packets.
+
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::mem::{size_of, zeroed};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+use std::ops::Range;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use macaddr::MacAddr6;
+use socket2::Socket;
+
+use crate::addressing::interface_index;
+use crate::discovery::POLL_INTERVAL;
+use crate::{Received, Transport, UdpTransport};
+
+// Ethernet types
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const IPPROTO_UDP: u8 = 17;
+
+// Large enough for any frame
+const FRAME_BUFFER_SIZE: usize = 65536;
+
+// Length of the filter's instructions for one IP header position
+const FILTER_IP_LEN: usize = 13;
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter { code: code as u16, jt: 0, jf: 0, k }
+}
+
+// Append a conditional jump to the absolute instruction indexes `jt` and
+// `jf`, which must follow it
+fn bpf_jump(program: &mut Vec<libc::sock_filter>, code: u32, k: u32, jt: usize, jf: usize) {
+    let next = program.len() + 1;
+    program.push(libc::sock_filter { code: (libc::BPF_JMP | code) as u16, jt: (jt - next) as u8, jf: (jf - next) as u8, k });
+}
+
+// Classic BPF program passing only frames which could be UDP datagrams to
+// `port`, untagged or with up to two VLAN tags, so the kernel drops the rest
+// of the traffic on the interface rather than copying it to the socket.
+// `parse_frame` still checks each frame passed.
+fn mndp_filter(port: u16) -> Vec<libc::sock_filter> {
+    let accept = 3 * (FILTER_IP_LEN + 1) + 4;
+    let reject = accept + 1;
+    let mut program = Vec::new();
+
+    for (tags, ethertype) in [(0, 12), (1, 16), (2, 20)] {
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, ethertype));
+        if tags < 2 {
+            let here = program.len();
+            let tagged = here + 2 + FILTER_IP_LEN;
+            bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, ETHERTYPE_VLAN.into(), tagged, here + 1);
+            bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, ETHERTYPE_QINQ.into(), tagged, here + 2);
+        }
+
+        // With the ethertype loaded, check the IP header after it
+        let ip = ethertype + 2;
+        let start = program.len();
+        let (ipv4, ipv6) = (start + 2, start + 9);
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, ETHERTYPE_IPV4.into(), ipv4, start + 1);
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, ETHERTYPE_IPV6.into(), ipv6, reject);
+        // IPv4: UDP, not a fragment, destination port after the options
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, ip + 9));
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, IPPROTO_UDP.into(), start + 4, reject);
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, ip + 6));
+        bpf_jump(&mut program, libc::BPF_JSET | libc::BPF_K, 0x3fff, reject, start + 6);
+        program.push(bpf_stmt(libc::BPF_LDX | libc::BPF_B | libc::BPF_MSH, ip));
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_IND, ip + 2));
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, port.into(), accept, reject);
+        // IPv6: UDP directly after the fixed header
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, ip + 6));
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, IPPROTO_UDP.into(), start + 11, reject);
+        program.push(bpf_stmt(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, ip + 42));
+        bpf_jump(&mut program, libc::BPF_JEQ | libc::BPF_K, port.into(), accept, reject);
+    }
+
+    program.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, FRAME_BUFFER_SIZE as u32));
+    program.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, 0));
+    program
+}
+
+/// Transport which captures MNDP packets from all frames on an interface,
+/// recording the VLAN ID of tagged frames; e.g. to hear neighbors on every
+/// VLAN of a trunk port. The VLAN is read from the kernel when it has
+/// stripped the tag (VLAN offload) and from the frame otherwise; with
+/// stacked (QinQ) tags the innermost is used.
+///
+/// Opening requires `CAP_NET_RAW`. Solicitations are sent as untagged IPv4
+/// broadcasts via a UDP socket bound to the interface.
+pub struct RawTransport {
+    socket: Socket,
+    index: u32,
+    port: u16,
+    frame: Mutex<Vec<u8>>,
+    sender: UdpTransport,
+}
+
+impl RawTransport {
+    /// Capture MNDP packets sent to `port` on the named interface.
+    pub fn open(interface: &str, port: u16) -> io::Result<RawTransport> {
+        let index = interface_index(interface)?;
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC, protocol.into()) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { Socket::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_ll = unsafe { zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = protocol;
+        addr.sll_ifindex = index as i32;
+        let addr_ptr = &addr as *const libc::sockaddr_ll as *const libc::sockaddr;
+        if unsafe { libc::bind(fd, addr_ptr, size_of::<libc::sockaddr_ll>() as libc::socklen_t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Have the kernel report VLAN tags it stripped from received frames
+        let enable: libc::c_int = 1;
+        let enable_ptr = &enable as *const libc::c_int as *const libc::c_void;
+        let enable_len = size_of::<libc::c_int>() as libc::socklen_t;
+        if unsafe { libc::setsockopt(fd, libc::SOL_PACKET, libc::PACKET_AUXDATA, enable_ptr, enable_len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Frames queued before the filter is attached are still received,
+        // but are dropped by `parse_frame`
+        let filter = mndp_filter(port);
+        let program = libc::sock_fprog { len: filter.len() as u16, filter: filter.as_ptr() as *mut libc::sock_filter };
+        let program_ptr = &program as *const libc::sock_fprog as *const libc::c_void;
+        let program_len = size_of::<libc::sock_fprog>() as libc::socklen_t;
+        if unsafe { libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, program_ptr, program_len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        udp.set_broadcast(true)?;
+        let sender = UdpTransport::new(udp, SocketAddrV4::new(Ipv4Addr::BROADCAST, port).into())
+            .bind_interface(interface)?;
+
+        Ok(RawTransport {
+            socket,
+            index,
+            port,
+            frame: Mutex::new(vec![0u8; FRAME_BUFFER_SIZE]),
+            sender,
+        })
+    }
+
+    // Receive one frame, returning its length and any VLAN ID stripped by
+    // the kernel, or `None` for frames sent by this host
+    fn recv_frame(&self, frame: &mut [u8]) -> io::Result<Option<(usize, Option<u16>)>> {
+        let mut addr: libc::sockaddr_ll = unsafe { zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: frame.as_mut_ptr() as *mut libc::c_void,
+            iov_len: frame.len(),
+        };
+        let mut control = [0u64; 8];
+
+        let mut msg: libc::msghdr = unsafe { zeroed() };
+        msg.msg_name = &mut addr as *mut libc::sockaddr_ll as *mut libc::c_void;
+        msg.msg_namelen = size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = size_of::<[u64; 8]>() as _;
+
+        let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if addr.sll_pkttype == libc::PACKET_OUTGOING {
+            return Ok(None);
+        }
+
+        let mut vlan = None;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        while let Some(header) = unsafe { cmsg.as_ref() } {
+            if header.cmsg_level == libc::SOL_PACKET && header.cmsg_type == libc::PACKET_AUXDATA {
+                let aux = unsafe { (libc::CMSG_DATA(cmsg) as *const libc::tpacket_auxdata).read_unaligned() };
+                if aux.tp_status & libc::TP_STATUS_VLAN_VALID != 0 {
+                    vlan = Some(aux.tp_vlan_tci & 0x0fff);
+                }
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+
+        Ok(Some((len as usize, vlan)))
+    }
+}
+
+impl Transport for RawTransport {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv(buf).map(|received| (received.len, received.source))
+    }
+
+    // Frames other than MNDP packets are skipped, returning `WouldBlock` if
+    // there were only such frames for the poll interval
+    fn recv(&self, buf: &mut [u8]) -> io::Result<Received> {
+        let mut frame = self.frame.lock().unwrap();
+        let start = Instant::now();
+
+        while start.elapsed() < POLL_INTERVAL {
+            let (len, stripped_vlan) = match self.recv_frame(&mut frame)? {
+                Some(received) => received,
+                None => continue,
+            };
+            if let Some((payload, source, vlan)) = parse_frame(&frame[..len], self.port, self.index) {
+                let len = payload.len().min(buf.len());
+                buf[..len].copy_from_slice(&frame[payload.start..payload.start + len]);
+                return Ok(Received {
+                    len,
+                    source,
+                    vlan: vlan.or(stripped_vlan),
+                    scope_id: Some(self.index),
+                    link_source: <[u8; 6]>::try_from(&frame[6..12]).ok().map(MacAddr6::from),
+                });
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::WouldBlock, "no MNDP packet received"))
+    }
+
+    fn broadcast(&self, buf: &[u8]) -> io::Result<()> {
+        self.sender.broadcast(buf)
+    }
+}
+
+fn be16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+// Locate the payload of a UDP datagram to `port` in an Ethernet frame,
+// returning it with the source address and the VLAN ID of any 802.1Q tag.
+// `index` is the scope of link-local IPv6 sources.
+fn parse_frame(frame: &[u8], port: u16, index: u32) -> Option<(Range<usize>, SocketAddr, Option<u16>)> {
+    let mut offset = 12;
+    let mut ethertype = be16(frame, offset)?;
+    let mut vlan = None;
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        vlan = Some(be16(frame, offset + 2)? & 0x0fff);
+        offset += 4;
+        ethertype = be16(frame, offset)?;
+    }
+    offset += 2;
+
+    let ip = frame.get(offset..)?;
+    let (udp_offset, ip_end, source_ip) = match ethertype {
+        ETHERTYPE_IPV4 => {
+            let header_len = usize::from(ip.first()? & 0x0f) * 4;
+            // Skip fragments, which cannot be reassembled here
+            let fragment = be16(ip, 6)? & 0x3fff;
+            if *ip.get(9)? != IPPROTO_UDP || fragment != 0 || ip.len() < 20 {
+                return None;
+            }
+            let source: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            (header_len, usize::from(be16(ip, 2)?), SocketAddr::V4(SocketAddrV4::new(source.into(), 0)))
+        }
+        ETHERTYPE_IPV6 => {
+            // Extension headers are not followed
+            if *ip.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(ip.get(8..24)?).ok()?);
+            let scope = if source.segments()[0] & 0xffc0 == 0xfe80 { index } else { 0 };
+            (40, 40 + usize::from(be16(ip, 4)?), SocketAddr::V6(SocketAddrV6::new(source, 0, 0, scope)))
+        }
+        _ => return None,
+    };
+
+    let udp = ip.get(udp_offset..ip_end.min(ip.len()))?;
+    if be16(udp, 2)? != port {
+        return None;
+    }
+    let udp_len = usize::from(be16(udp, 4)?);
+    if udp_len < 8 || udp_len > udp.len() {
+        return None;
+    }
+
+    let mut source = source_ip;
+    source.set_port(be16(udp, 0)?);
+    let start = offset + udp_offset + 8;
+    Some((start..start + udp_len - 8, source, vlan))
+}
+
+#[test]
+fn test_parse_frame() {
+    use crate::MNDP_PORT;
+
+    // Broadcast from 10.0.0.1:5678 tagged with VLAN 100, carrying a
+    // solicitation
+    let frame = hex::decode(concat!(
+        "ffffffffffff000c42b8a9f3", "81000064", "0800",
+        "4500002000004000401100000a0000010affffff",
+        "162e162e000c0000", "00000000",
+    )).unwrap();
+    let (payload, source, vlan) = parse_frame(&frame, MNDP_PORT, 2).unwrap();
+    assert_eq!(&frame[payload], &[0, 0, 0, 0]);
+    assert_eq!(source, "10.0.0.1:5678".parse().unwrap());
+    assert_eq!(vlan, Some(100));
+
+    // Untagged, to another port
+    let frame = hex::decode(concat!(
+        "ffffffffffff000c42b8a9f3", "0800",
+        "4500002000004000401100000a0000010affffff",
+        "162e0035000c0000", "00000000",
+    )).unwrap();
+    assert_eq!(parse_frame(&frame, MNDP_PORT, 2), None);
+
+    // IPv6 link-local source gets the interface as its scope
+    let frame = hex::decode(concat!(
+        "333300000001000c42b8a9f3", "86dd",
+        "60000000000c1101", "fe80000000000000020c42fffeb8a9f3", "ff020000000000000000000000000001",
+        "162e162e000c0000", "00000000",
+    )).unwrap();
+    let (_, source, vlan) = parse_frame(&frame, MNDP_PORT, 2).unwrap();
+    assert_eq!(source, "[fe80::20c:42ff:feb8:a9f3%2]:5678".parse().unwrap());
+    assert_eq!(vlan, None);
+}
+
+#[test]
+fn test_mndp_filter() {
+    use crate::MNDP_PORT;
+
+    // Run the filter over a frame as the kernel would, for the instructions
+    // it uses
+    fn run(program: &[libc::sock_filter], frame: &[u8]) -> u32 {
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0);
+        loop {
+            let insn = program[pc];
+            let k = insn.k as usize;
+            let code = u32::from(insn.code);
+            pc += 1;
+            match code {
+                c if c == libc::BPF_LD | libc::BPF_H | libc::BPF_ABS => match be16(frame, k) {
+                    Some(value) => a = value.into(),
+                    None => return 0,
+                },
+                c if c == libc::BPF_LD | libc::BPF_H | libc::BPF_IND => match be16(frame, x as usize + k) {
+                    Some(value) => a = value.into(),
+                    None => return 0,
+                },
+                c if c == libc::BPF_LD | libc::BPF_B | libc::BPF_ABS => match frame.get(k) {
+                    Some(value) => a = (*value).into(),
+                    None => return 0,
+                },
+                c if c == libc::BPF_LDX | libc::BPF_B | libc::BPF_MSH => match frame.get(k) {
+                    Some(value) => x = u32::from(value & 0x0f) * 4,
+                    None => return 0,
+                },
+                c if c == libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K || c == libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K => {
+                    let taken = if c & libc::BPF_JSET != 0 { a & insn.k != 0 } else { a == insn.k };
+                    pc += usize::from(if taken { insn.jt } else { insn.jf });
+                }
+                c if c == libc::BPF_RET | libc::BPF_K => return insn.k,
+                c => panic!("unexpected instruction {:#x}", c),
+            }
+        }
+    }
+
+    let program = mndp_filter(MNDP_PORT);
+    let udp = "4500002000004000401100000a0000010affffff";
+    let payload = "000c0000 00000000";
+    let frames = [
+        // Untagged, tagged and QinQ IPv4 to MNDP_PORT
+        (format!("ffffffffffff000c42b8a9f3 0800 {} 162e162e{}", udp, payload), true),
+        (format!("ffffffffffff000c42b8a9f3 81000064 0800 {} 162e162e{}", udp, payload), true),
+        (format!("ffffffffffff000c42b8a9f3 88a80010 81000064 0800 {} 162e162e{}", udp, payload), true),
+        // IPv4 with options
+        (format!("ffffffffffff000c42b8a9f3 0800 4600002400004000401100000a0000010affffff01010101 162e162e{}", payload), true),
+        // IPv6
+        (format!(
+            "333300000001000c42b8a9f3 86dd 60000000000c1101 fe80000000000000020c42fffeb8a9f3 ff020000000000000000000000000001 162e162e{}",
+            payload,
+        ), true),
+        // Another port, TCP, a fragment and ARP
+        (format!("ffffffffffff000c42b8a9f3 0800 {} 162e0035{}", udp, payload), false),
+        (format!("ffffffffffff000c42b8a9f3 0800 4500002000004000400600000a0000010affffff 162e162e{}", payload), false),
+        (format!("ffffffffffff000c42b8a9f3 0800 4500002000002001401100000a0000010affffff 162e162e{}", payload), false),
+        (String::from("ffffffffffff000c42b8a9f3 0806 0001080006040001"), false),
+    ];
+    for (frame, expected) in &frames {
+        let bytes = hex::decode(frame.replace(' ', "")).unwrap();
+        assert_eq!(run(&program, &bytes) != 0, *expected, "{}", frame);
+        assert_eq!(parse_frame(&bytes, MNDP_PORT, 2).is_some(), *expected, "{}", frame);
+    }
+}
@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+// Characters which can reorder displayed text (bidirectional overrides and
+// isolates), allowing one string to masquerade as another
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Escape control characters in a string received from the network, so it
+/// can be shown on a terminal without injecting escape sequences; e.g. ESC
+/// becomes '\x1b'. Backslashes are escaped too so the result is unambiguous.
+///
+/// The string is borrowed unchanged in the common case where there is nothing
+/// to escape. The fields of `Neighbor` always hold the raw values; its
+/// `Display` implementation uses this function.
+pub fn sanitize(value: &str) -> Cow<'_, str> {
+    let unsafe_char = |c: char| c.is_control() || is_bidi_control(c) || c == '\\';
+    if !value.chars().any(unsafe_char) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if (c as u32) < 0x80 && c.is_control() => {
+                let _ = write!(escaped, "\\x{:02x}", c as u32);
+            }
+            c if unsafe_char(c) => {
+                let _ = write!(escaped, "\\u{{{:x}}}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[test]
+fn test_sanitize() {
+    assert!(matches!(sanitize("router1 (ünïcode)"), Cow::Borrowed(_)));
+    assert_eq!(sanitize("evil\x1b[2J\x1b]0;title\x07"), "evil\\x1b[2J\\x1b]0;title\\x07");
+    assert_eq!(sanitize("a\tb\nc\\d"), "a\\tb\\nc\\\\d");
+    assert_eq!(sanitize("\u{9b}31m"), "\\u{9b}31m");
+    assert_eq!(sanitize("abc\u{202e}fed"), "abc\\u{202e}fed");
+}
@@ -0,0 +1,175 @@
+use std::collections::btree_map::{self, BTreeMap};
+use std::iter::FromIterator;
+
+use macaddr::MacAddr6;
+
+use crate::{DiscoveredNeighbor, Neighbor, NeighborTable};
+
+/// Set of neighbors keyed by MAC address, independent of any live table;
+/// e.g. the result of a survey run. Neighbors without a MAC address cannot
+/// be keyed and are ignored.
+///
+/// Where a neighbor is in both operands of `union` or `intersection`, the
+/// fields present in the right operand take precedence (see
+/// `Neighbor::merge`).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NeighborSet {
+    entries: BTreeMap<MacAddr6, Neighbor>,
+}
+
+impl NeighborSet {
+    /// Create a new empty set.
+    pub fn new() -> NeighborSet {
+        Default::default()
+    }
+
+    /// Number of neighbors in the set.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether a neighbor with the given MAC address is in the set.
+    pub fn contains(&self, mac: &MacAddr6) -> bool {
+        self.entries.contains_key(mac)
+    }
+
+    /// Look up a neighbor by MAC address.
+    pub fn get(&self, mac: &MacAddr6) -> Option<&Neighbor> {
+        self.entries.get(mac)
+    }
+
+    /// Iterate over the neighbors in MAC address order.
+    pub fn iter(&self) -> btree_map::Values<'_, MacAddr6, Neighbor> {
+        self.entries.values()
+    }
+
+    /// Add a neighbor, merging it into any neighbor with the same MAC
+    /// address. Returns `false` if the neighbor has no MAC address.
+    pub fn insert(&mut self, neighbor: Neighbor) -> bool {
+        match neighbor.mac_address {
+            Some(mac) => {
+                match self.entries.entry(mac) {
+                    btree_map::Entry::Occupied(mut entry) => entry.get_mut().merge(&neighbor),
+                    btree_map::Entry::Vacant(entry) => {
+                        entry.insert(neighbor);
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a neighbor by MAC address, returning it if it was present.
+    pub fn remove(&mut self, mac: &MacAddr6) -> Option<Neighbor> {
+        self.entries.remove(mac)
+    }
+
+    /// Neighbors in either set.
+    pub fn union(&self, other: &NeighborSet) -> NeighborSet {
+        let mut set = self.clone();
+        set.extend(other.iter().cloned());
+        set
+    }
+
+    /// Neighbors in both sets.
+    pub fn intersection(&self, other: &NeighborSet) -> NeighborSet {
+        let entries = self.entries.iter()
+            .filter_map(|(mac, neighbor)| other.get(mac).map(|theirs| {
+                let mut neighbor = neighbor.clone();
+                neighbor.merge(theirs);
+                (*mac, neighbor)
+            }))
+            .collect();
+        NeighborSet { entries }
+    }
+
+    /// Neighbors in `self` but not in `other`; e.g. those that disappeared
+    /// since an earlier run when `self` is the earlier set.
+    pub fn difference(&self, other: &NeighborSet) -> NeighborSet {
+        let entries = self.entries.iter()
+            .filter(|(mac, _)| !other.contains(mac))
+            .map(|(mac, neighbor)| (*mac, neighbor.clone()))
+            .collect();
+        NeighborSet { entries }
+    }
+}
+
+impl Extend<Neighbor> for NeighborSet {
+    fn extend<I: IntoIterator<Item = Neighbor>>(&mut self, iter: I) {
+        for neighbor in iter {
+            self.insert(neighbor);
+        }
+    }
+}
+
+impl FromIterator<Neighbor> for NeighborSet {
+    fn from_iter<I: IntoIterator<Item = Neighbor>>(iter: I) -> NeighborSet {
+        let mut set = NeighborSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl FromIterator<DiscoveredNeighbor> for NeighborSet {
+    fn from_iter<I: IntoIterator<Item = DiscoveredNeighbor>>(iter: I) -> NeighborSet {
+        iter.into_iter().map(|discovered| discovered.neighbor).collect()
+    }
+}
+
+impl From<&NeighborTable> for NeighborSet {
+    fn from(table: &NeighborTable) -> NeighborSet {
+        table.iter().map(|discovered| discovered.neighbor.clone()).collect()
+    }
+}
+
+impl IntoIterator for NeighborSet {
+    type Item = Neighbor;
+    type IntoIter = btree_map::IntoValues<MacAddr6, Neighbor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_values()
+    }
+}
+
+#[test]
+fn test_neighbor_set_operations() {
+    let neighbor = |mac: u8, identity: &str| Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, mac])
+        .identity(identity)
+        .build();
+
+    let before: NeighborSet = vec![neighbor(1, "a"), neighbor(2, "b")].into_iter().collect();
+    let after: NeighborSet = vec![neighbor(2, "b2"), neighbor(3, "c")].into_iter().collect();
+    let mac = |mac: u8| MacAddr6::new(0, 0, 0, 0, 0, mac);
+
+    let union = before.union(&after);
+    assert_eq!(union.len(), 3);
+    assert_eq!(union.get(&mac(2)).unwrap().identity.as_deref(), Some("b2"));
+
+    let intersection = before.intersection(&after);
+    assert_eq!(intersection.iter().cloned().collect::<Vec<_>>(), vec![neighbor(2, "b2")]);
+
+    assert_eq!(before.difference(&after).into_iter().collect::<Vec<_>>(), vec![neighbor(1, "a")]);
+    assert_eq!(after.difference(&before).into_iter().collect::<Vec<_>>(), vec![neighbor(3, "c")]);
+}
+
+#[test]
+fn test_neighbor_set_merge() {
+    let mut set = NeighborSet::new();
+    assert!(!set.insert(Neighbor::builder().identity("no-mac").build()));
+
+    let mac = MacAddr6::new(0, 0, 0, 0, 0, 1);
+    assert!(set.insert(Neighbor::builder().mac_address(mac).identity("a").version("1").build()));
+    assert!(set.insert(Neighbor::builder().mac_address(mac).version("2").build()));
+
+    // Fields missing in the later neighbor are kept
+    let merged = set.get(&mac).unwrap();
+    assert_eq!(merged.identity.as_deref(), Some("a"));
+    assert_eq!(merged.version.as_deref(), Some("2"));
+}
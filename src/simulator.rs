@@ -0,0 +1,344 @@
+//! Simulator which announces a population of fabricated neighbors, for
+//! load-testing and demonstrating consumers without MikroTik hardware.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use crate::announce::jittered;
+use crate::discovery::{broadcast_all, default_transports, recv_backoff, POLL_INTERVAL};
+use crate::{AddressFamily, Neighbor, Packet, RateLimiter, Transport, ANNOUNCE_INTERVAL, DEFAULT_MAX_PACKET_SIZE, MNDP_PORT, SOLICIT};
+
+const BOARDS: [&str; 6] = ["RB5009UG+S+", "CCR2004-16G-2S+", "CRS326-24G-2S+", "hAP ac^2", "RB4011iGS+", "hEX S"];
+const VERSIONS: [&str; 4] = ["7.12.1 (stable)", "7.14.3 (stable)", "7.15 (stable)", "6.49.10 (long-term)"];
+const INTERFACES: [&str; 4] = ["ether1", "ether2", "bridge", "sfp-sfpplus1"];
+
+// Most uptime a fabricated neighbor starts with
+const MAX_UPTIME: u64 = 90 * 24 * 60 * 60;
+
+// Deterministic generator (SplitMix64), so a seed always fabricates the
+// same neighbors
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn pick<'a>(&mut self, values: &[&'a str]) -> &'a str {
+        values[(self.next() % values.len() as u64) as usize]
+    }
+}
+
+// State shared between the simulator handle and its threads
+struct Shared {
+    transports: Vec<Box<dyn Transport>>,
+    neighbors: Vec<Neighbor>,
+    sequences: Vec<AtomicU16>,
+    started: Instant,
+    max_packet_size: usize,
+    limiter: RateLimiter,
+    interval: Duration,
+    jitter: Duration,
+    running: AtomicBool,
+}
+
+impl Shared {
+    // Announce one neighbor, with its uptime advanced since the start
+    fn announce(&self, index: usize) -> io::Result<()> {
+        let mut neighbor = self.neighbors[index].clone();
+        neighbor.uptime = neighbor.uptime.map(|uptime| uptime + self.started.elapsed());
+        let mut packet = Packet::from_neighbor(&neighbor);
+        packet.set_sequence(self.sequences[index].fetch_add(1, Ordering::Relaxed));
+        let bytes: Bytes = packet.to_bytes_with_limit(self.max_packet_size);
+        broadcast_all(self.transports.iter().map(|transport| transport.as_ref()), &bytes)
+    }
+
+    // Have every neighbor reply to solicitations received on `transport`
+    fn receive(&self, transport: &dyn Transport) {
+        let mut buf = [0u8; 64];
+
+        while self.running.load(Ordering::Relaxed) {
+            let len = match transport.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(e) => {
+                    recv_backoff(&e);
+                    continue;
+                }
+            };
+
+            if Packet::from_bytes(Bytes::copy_from_slice(&buf[..len])) == Ok(SOLICIT) && self.limiter.try_acquire() {
+                for index in 0..self.neighbors.len() {
+                    let _ = self.announce(index);
+                }
+            }
+        }
+    }
+
+    // Announce each neighbor every interval, starting at a random point in
+    // the first so the population does not announce in step
+    fn periodic(&self) {
+        let mut schedule: BinaryHeap<Reverse<(Instant, usize)>> = (0..self.neighbors.len())
+            .map(|index| Reverse((self.started + jittered(self.interval / 2, self.interval / 2), index)))
+            .collect();
+
+        while self.running.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            while let Some(&Reverse((next, index))) = schedule.peek() {
+                if next > now {
+                    break;
+                }
+                schedule.pop();
+                let _ = self.announce(index);
+                schedule.push(Reverse((next.max(now) + jittered(self.interval, self.jitter), index)));
+            }
+
+            let next = schedule.peek().map_or(now + POLL_INTERVAL, |Reverse((next, _))| *next);
+            thread::sleep(next.saturating_duration_since(Instant::now()).min(POLL_INTERVAL));
+        }
+    }
+}
+
+/// Running simulator, which announces a population of fabricated neighbors
+/// at their own intervals and has them all reply to solicitations. Its
+/// neighbors look like MikroTik devices, but use a locally administered MAC
+/// address prefix (02:4D:4E) and the benchmarking (198.18.0.0/15) and
+/// documentation (2001:db8::/32) address ranges. The simulator stops when
+/// it is dropped.
+pub struct Simulator {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Builder structure for a `Simulator`.
+pub struct SimulatorBuilder {
+    count: usize,
+    seed: Option<u64>,
+    port: u16,
+    family: AddressFamily,
+    interfaces: Vec<String>,
+    namespace: Option<String>,
+    max_packet_size: usize,
+    limiter: RateLimiter,
+    interval: Duration,
+    jitter: Duration,
+    transports: Vec<Box<dyn Transport>>,
+}
+
+impl Simulator {
+    /// Create a new simulator builder.
+    pub fn builder() -> SimulatorBuilder {
+        SimulatorBuilder {
+            count: 10,
+            seed: None,
+            port: MNDP_PORT,
+            family: AddressFamily::Any,
+            interfaces: Vec::new(),
+            namespace: None,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            limiter: RateLimiter::default(),
+            interval: ANNOUNCE_INTERVAL,
+            jitter: Duration::from_secs(5),
+            transports: Vec::new(),
+        }
+    }
+
+    /// Fabricate `count` neighbors; the same seed always gives the same
+    /// neighbors. Each has a unique MAC address, IPv4 and IPv6 address and
+    /// identity.
+    pub fn fabricate(count: usize, seed: u64) -> Vec<Neighbor> {
+        let mut random = SplitMix64(seed);
+        (0..count as u32)
+            .map(|index| {
+                let [_, a, b, c] = index.to_be_bytes();
+                let host = index + 1;
+                let software_id = random.next();
+                Neighbor::builder()
+                    .mac_address([0x02, 0x4d, 0x4e, a, b, c])
+                    .identity(format!("sim-{:06}", host))
+                    .platform("MikroTik")
+                    .version(random.pick(&VERSIONS))
+                    .board(random.pick(&BOARDS))
+                    .interface_name(random.pick(&INTERFACES))
+                    .ipv4_address(Ipv4Addr::from(u32::from(Ipv4Addr::new(198, 18, 0, 0)) + host))
+                    .ipv6_address(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, (host >> 16) as u16, host as u16))
+                    .software_id(format!("{:04X}-{:04X}", software_id >> 48, (software_id >> 32) & 0xffff))
+                    .uptime(Duration::from_secs(random.next() % MAX_UPTIME))
+                    .build()
+            })
+            .collect()
+    }
+
+    /// The neighbors being announced, with their uptimes at the start.
+    pub fn neighbors(&self) -> &[Neighbor] {
+        &self.shared.neighbors
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl SimulatorBuilder {
+    /// Set the number of neighbors (default 10).
+    pub fn count(mut self, value: usize) -> Self {
+        self.count = value;
+        self
+    }
+
+    /// Set the seed the neighbors are fabricated from (default random).
+    pub fn seed(mut self, value: u64) -> Self {
+        self.seed = Some(value);
+        self
+    }
+
+    /// Set the UDP port for the default transports (default `MNDP_PORT`).
+    pub fn port(mut self, value: u16) -> Self {
+        self.port = value;
+        self
+    }
+
+    /// Restrict the default transports to one address family (default
+    /// `AddressFamily::Any`).
+    pub fn address_family(mut self, value: AddressFamily) -> Self {
+        self.family = value;
+        self
+    }
+
+    /// Announce only on the named network interface; e.g. 'ether1'. May be
+    /// called more than once (default all interfaces).
+    pub fn interface<S: Into<String>>(mut self, value: S) -> Self {
+        self.interfaces.push(value.into());
+        self
+    }
+
+    /// Open the default transports in a Linux network namespace, given by
+    /// name or path (see `in_namespace`), instead of the current one.
+    #[cfg(target_os = "linux")]
+    pub fn namespace<S: Into<String>>(mut self, value: S) -> Self {
+        self.namespace = Some(value.into());
+        self
+    }
+
+    /// Set the largest announcement sent (default
+    /// `DEFAULT_MAX_PACKET_SIZE`).
+    pub fn max_packet_size(mut self, value: usize) -> Self {
+        self.max_packet_size = value;
+        self
+    }
+
+    /// Limit the rate of replies to solicitations, each of which announces
+    /// every neighbor (default `RateLimiter::default()`).
+    pub fn rate_limit(mut self, value: RateLimiter) -> Self {
+        self.limiter = value;
+        self
+    }
+
+    /// Set the interval between each neighbor's announcements, which must
+    /// not be zero (default `ANNOUNCE_INTERVAL`).
+    pub fn interval(mut self, value: Duration) -> Self {
+        self.interval = value;
+        self
+    }
+
+    /// Vary each interval randomly by up to this much either way (default
+    /// 5 seconds).
+    pub fn jitter(mut self, value: Duration) -> Self {
+        self.jitter = value;
+        self
+    }
+
+    /// Use a custom transport; e.g. a `MockTransport`. If any are given, the
+    /// default UDP transports are not created.
+    pub fn transport<T: Transport + 'static>(mut self, value: T) -> Self {
+        self.transports.push(Box::new(value));
+        self
+    }
+
+    /// Fabricate the neighbors, open the transports and start announcing.
+    pub fn start(self) -> io::Result<Simulator> {
+        if self.interval.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "announce interval must not be zero"));
+        }
+        let mut transports = self.transports;
+        if transports.is_empty() && self.interfaces.is_empty() {
+            transports = default_transports(self.port, self.family, None, self.namespace.as_deref())?;
+        } else if transports.is_empty() {
+            for interface in &self.interfaces {
+                transports.extend(default_transports(self.port, self.family, Some(interface), self.namespace.as_deref())?);
+            }
+        }
+        let seed = self.seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+        let neighbors = Simulator::fabricate(self.count, seed);
+
+        let shared = Arc::new(Shared {
+            transports,
+            sequences: neighbors.iter().map(|_| AtomicU16::new(0)).collect(),
+            neighbors,
+            started: Instant::now(),
+            max_packet_size: self.max_packet_size,
+            limiter: self.limiter,
+            interval: self.interval,
+            jitter: self.jitter,
+            running: AtomicBool::new(true),
+        });
+
+        let mut threads = Vec::new();
+        for index in 0..shared.transports.len() {
+            let shared = shared.clone();
+            threads.push(thread::spawn(move || shared.receive(shared.transports[index].as_ref())));
+        }
+        let periodic = shared.clone();
+        threads.push(thread::spawn(move || periodic.periodic()));
+
+        Ok(Simulator { shared, threads })
+    }
+}
+
+#[test]
+fn test_simulator() {
+    use std::collections::HashSet;
+    use crate::MockTransport;
+
+    let neighbors = Simulator::fabricate(300, 1);
+    assert_eq!(neighbors, Simulator::fabricate(300, 1));
+    assert_eq!(neighbors.iter().map(|neighbor| neighbor.mac_address).collect::<HashSet<_>>().len(), 300);
+    assert_eq!(neighbors[299].ipv4_address, Some(Ipv4Addr::new(198, 18, 1, 44)));
+
+    // Every neighbor announces within the first interval
+    let transport = MockTransport::new();
+    let simulator = Simulator::builder()
+        .count(5)
+        .seed(1)
+        .interval(Duration::from_millis(200))
+        .jitter(Duration::ZERO)
+        .transport(transport.clone())
+        .start()
+        .unwrap();
+    thread::sleep(Duration::from_millis(600));
+    let announced: HashSet<_> = transport.take_sent().into_iter()
+        .map(|bytes| Packet::from_bytes(bytes).unwrap().to_neighbor().mac_address)
+        .collect();
+    assert_eq!(announced, simulator.neighbors().iter().map(|neighbor| neighbor.mac_address).collect());
+
+    let builder = Simulator::builder().interval(Duration::ZERO).transport(MockTransport::new());
+    assert_eq!(builder.start().err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+}
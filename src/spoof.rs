@@ -0,0 +1,170 @@
+//! Detection of spoofed or replayed announcements, from the sequence numbers
+//! each device counts up and the link-layer source of captured frames.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+use macaddr::MacAddr6;
+
+use crate::{DiscoveredNeighbor, DiscoveryEvent, Neighbor, NeighborTable, Received, Suspicion};
+
+// Latest sequence number announced by a MAC address, with the uptime it was
+// announced with to tell a reboot from a replay
+#[derive(Debug)]
+struct Latest {
+    sequence: u16,
+    uptime: Option<Duration>,
+    seen: SystemTime,
+}
+
+// Sequence numbers by MAC address and the path they arrived by (transport
+// and VLAN), remembered for the table's TTL. Devices send each announcement
+// on every path with the same sequence number, so paths are tracked apart.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceTracker {
+    latest: HashMap<(MacAddr6, usize, Option<u16>), Latest>,
+}
+
+impl SequenceTracker {
+    pub(crate) fn new() -> SequenceTracker {
+        SequenceTracker::default()
+    }
+
+    // Check an announcement received on the transport with index
+    // `transport`, about to be applied to `table`, returning a `Suspicious`
+    // event if it looks spoofed or replayed
+    pub(crate) fn check(&mut self, table: &NeighborTable, neighbor: &Neighbor, sequence: u16, received: &Received, transport: usize, now: SystemTime) -> Option<DiscoveryEvent> {
+        let reason = self.suspicion(table.ttl(), neighbor, sequence, received, transport, now)?;
+        let mac = neighbor.mac_address?;
+        Some(DiscoveryEvent::Suspicious {
+            neighbor: DiscoveredNeighbor {
+                neighbor: neighbor.clone(),
+                first_seen: table.get(&mac).map_or(now, |known| known.first_seen),
+                last_seen: now,
+                vlan: received.vlan,
+                scope_id: received.scope_id,
+            },
+            reason,
+        })
+    }
+
+    fn suspicion(&mut self, ttl: Duration, neighbor: &Neighbor, sequence: u16, received: &Received, transport: usize, now: SystemTime) -> Option<Suspicion> {
+        let mac = neighbor.mac_address?;
+        if let Some(link_source) = received.link_source {
+            if link_source != mac {
+                return Some(Suspicion::MacMismatch);
+            }
+            match (neighbor.ipv4_address, received.source.ip()) {
+                (Some(announced), IpAddr::V4(source)) if !source.is_unspecified() && source != announced => {
+                    return Some(Suspicion::IpMismatch);
+                }
+                _ => {}
+            }
+        }
+
+        // A clock stepped backwards counts as within the TTL
+        let path = (mac, transport, received.vlan);
+        let latest = self.latest.get(&path).filter(|latest| now.duration_since(latest.seen).unwrap_or_default() < ttl);
+        let suspicion = match latest {
+            // The sequence restarts when the device reboots
+            Some(latest) if latest.uptime.zip(neighbor.uptime).is_some_and(|(before, after)| after < before) => None,
+            Some(latest) if latest.sequence == sequence => Some(Suspicion::RepeatedSequence),
+            // Compared as serial numbers, so counting past 65535 is in order
+            Some(latest) if (sequence.wrapping_sub(latest.sequence) as i16) < 0 => Some(Suspicion::OutOfOrderSequence),
+            _ => None,
+        };
+        if suspicion.is_none() {
+            self.latest.insert(path, Latest { sequence, uptime: neighbor.uptime, seen: now });
+        }
+        suspicion
+    }
+
+    // Forget MAC addresses not heard from within the TTL
+    pub(crate) fn prune(&mut self, ttl: Duration, now: SystemTime) {
+        self.latest.retain(|_, latest| now.duration_since(latest.seen).unwrap_or_default() < ttl);
+    }
+}
+
+#[test]
+fn test_sequence_tracker() {
+    use crate::{Discovery, MockClock, MockTransport, Packet};
+
+    let mac = MacAddr6::new(0, 0, 0, 0, 0, 1);
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let transport = MockTransport::new();
+    let discovery = Discovery::builder()
+        .transport(transport.clone())
+        .clock(MockClock::new(start))
+        .detect_spoofing(true)
+        .start()
+        .unwrap();
+    let events = discovery.subscribe();
+    let source = "192.0.2.1:5678".parse().unwrap();
+    for (sequence, uptime) in [(65535, 100), (0, 160), (0, 160), (65534, 220), (3, 5)] {
+        let neighbor = Neighbor::builder().mac_address(mac).uptime(Duration::from_secs(uptime)).build();
+        let mut packet = Packet::from_neighbor(&neighbor);
+        packet.set_sequence(sequence);
+        transport.inject(packet.to_bytes::<bytes::Bytes>(), source);
+    }
+
+    // Wrapping past 65535 and restarting after a reboot are not suspicious
+    let mut reasons = Vec::new();
+    while let Ok(event) = events.recv_timeout(Duration::from_secs(1)) {
+        if let DiscoveryEvent::Suspicious { reason, .. } = event {
+            reasons.push(reason);
+        }
+    }
+    assert_eq!(reasons, [Suspicion::RepeatedSequence, Suspicion::OutOfOrderSequence]);
+
+    // Captured frames must come from the announced addresses
+    let table = NeighborTable::new();
+    let mut tracker = SequenceTracker::new();
+    let neighbor = Neighbor::builder().mac_address(mac).ipv4_address([192, 0, 2, 1]).build();
+    let mut received = Received { len: 0, source, vlan: None, scope_id: None, link_source: Some(mac) };
+    assert_eq!(tracker.check(&table, &neighbor, 1, &received, 0, start), None);
+    received.source = "192.0.2.9:5678".parse().unwrap();
+    match tracker.check(&table, &neighbor, 2, &received, 0, start) {
+        Some(DiscoveryEvent::Suspicious { reason, .. }) => assert_eq!(reason, Suspicion::IpMismatch),
+        event => panic!("unexpected event {:?}", event),
+    }
+    received.link_source = Some(MacAddr6::new(0, 0, 0, 0, 0, 2));
+    assert!(matches!(tracker.check(&table, &neighbor, 3, &received, 0, start), Some(DiscoveryEvent::Suspicious { reason: Suspicion::MacMismatch, .. })));
+}
+
+#[test]
+fn test_sequence_tracker_paths() {
+    use crate::{Discovery, MockTransport, Packet};
+
+    let ipv4 = MockTransport::new();
+    let ipv6 = MockTransport::new();
+    let discovery = Discovery::builder()
+        .transport(ipv4.clone())
+        .transport(ipv6.clone())
+        .detect_spoofing(true)
+        .start()
+        .unwrap();
+    let events = discovery.subscribe();
+
+    // The copy of each announcement on the other transport is not a repeat,
+    // but a repeat on the same one still is
+    let neighbor = Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).uptime(Duration::from_secs(100)).build();
+    let packet = |sequence| {
+        let mut packet = Packet::from_neighbor(&neighbor);
+        packet.set_sequence(sequence);
+        packet.to_bytes::<bytes::Bytes>()
+    };
+    for sequence in [1, 2] {
+        ipv4.inject(packet(sequence), "192.0.2.1:5678".parse().unwrap());
+        ipv6.inject(packet(sequence), "[fe80::1]:5678".parse().unwrap());
+    }
+    ipv4.inject(packet(2), "192.0.2.1:5678".parse().unwrap());
+
+    let mut reasons = Vec::new();
+    while let Ok(event) = events.recv_timeout(Duration::from_secs(1)) {
+        if let DiscoveryEvent::Suspicious { reason, .. } = event {
+            reasons.push(reason);
+        }
+    }
+    assert_eq!(reasons, [Suspicion::RepeatedSequence]);
+}
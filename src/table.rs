@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, SocketAddrV6};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use macaddr::MacAddr6;
+
+use crate::{DiscoveryEvent, Neighbor, NeighborField, Packet, DEFAULT_MAX_PACKET_SIZE};
+
+/// Default time-to-live for table entries. RouterOS announces roughly every
+/// 60 seconds, so this allows for a couple of lost announcements.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(180);
+
+// Neighbor table file format identifier and version
+const TABLE_MAGIC: &[u8; 4] = b"MNDT";
+const TABLE_VERSION: u16 = 1;
+
+/// A `Neighbor` along with the times it was first and last heard from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiscoveredNeighbor {
+    /// Neighbor information from the most recent announcement.
+    pub neighbor: Neighbor,
+    /// Time the neighbor was first seen.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::rfc3339"))]
+    pub first_seen: SystemTime,
+    /// Time the most recent announcement was received.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_util::rfc3339"))]
+    pub last_seen: SystemTime,
+    /// VLAN the most recent announcement was received on, if the transport
+    /// captures link-layer frames and the frame was tagged. This describes
+    /// reception rather than the neighbor, so is not saved by
+    /// `NeighborTable::save`.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub vlan: Option<u16>,
+    /// Index of the interface the most recent announcement was received on,
+    /// if the transport knows it; the zone of a link-local `ipv6_address`.
+    /// Like `vlan`, this is not saved by `NeighborTable::save`.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub scope_id: Option<u32>,
+}
+
+impl DiscoveredNeighbor {
+    /// Socket address for connecting to the neighbor's IPv6 address on
+    /// `port`, with the zone set if the address is link-local. Returns `None`
+    /// if there is no IPv6 address, or it is link-local and the zone is
+    /// unknown.
+    pub fn ipv6_socket_addr(&self, port: u16) -> Option<SocketAddrV6> {
+        let addr = self.neighbor.ipv6_address?;
+        let scope_id = match addr.segments()[0] & 0xffc0 == 0xfe80 {
+            true => self.scope_id?,
+            false => 0,
+        };
+        Some(SocketAddrV6::new(addr, port, 0, scope_id))
+    }
+}
+
+/// Datagram received by a `Transport`; how a neighbor was heard, for
+/// `NeighborTable::update_received`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Received {
+    /// Length of the datagram.
+    pub len: usize,
+    /// Source address of the datagram.
+    pub source: SocketAddr,
+    /// VLAN ID from the 802.1Q tag of the frame carrying the datagram, for
+    /// transports which capture link-layer frames.
+    pub vlan: Option<u16>,
+    /// Index of the interface the datagram was received on, when known; the
+    /// zone of link-local IPv6 addresses on that link.
+    pub scope_id: Option<u32>,
+    /// Source MAC address of the frame carrying the datagram, for transports
+    /// which capture link-layer frames.
+    pub link_source: Option<MacAddr6>,
+}
+
+/// Table of discovered neighbors keyed by MAC address, with expiry of
+/// neighbors that have not been heard from within the TTL.
+#[derive(Clone, Debug)]
+pub struct NeighborTable {
+    ttl: Duration,
+    entries: HashMap<MacAddr6, DiscoveredNeighbor>,
+}
+
+impl Default for NeighborTable {
+    fn default() -> Self {
+        NeighborTable::with_ttl(DEFAULT_TTL)
+    }
+}
+
+impl NeighborTable {
+    /// Create a new empty table using `DEFAULT_TTL`.
+    pub fn new() -> NeighborTable {
+        Default::default()
+    }
+
+    /// Create a new empty table with the given TTL.
+    pub fn with_ttl(ttl: Duration) -> NeighborTable {
+        NeighborTable {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// TTL after which neighbors that have not been heard from are expired.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Number of neighbors in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the table contains no neighbors.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Look up a neighbor by MAC address.
+    pub fn get(&self, mac: &MacAddr6) -> Option<&DiscoveredNeighbor> {
+        self.entries.get(mac)
+    }
+
+    /// Iterate over all neighbors in the table (in arbitrary order).
+    pub fn iter(&self) -> impl Iterator<Item = &DiscoveredNeighbor> {
+        self.entries.values()
+    }
+
+    /// Record an announcement from `neighbor` received at `now`.
+    ///
+    /// Returns the resulting event, or `None` if nothing changed other than
+    /// uptime or the neighbor has no MAC address and cannot be tracked.
+    pub fn update(&mut self, neighbor: Neighbor, now: SystemTime) -> Option<DiscoveryEvent> {
+        self.update_with(neighbor, None, now)
+    }
+
+    /// Record an announcement from `neighbor` received by a transport at
+    /// `now`, keeping the details of its reception; otherwise as `update`.
+    pub fn update_received(&mut self, neighbor: Neighbor, received: &Received, now: SystemTime) -> Option<DiscoveryEvent> {
+        self.update_with(neighbor, Some(received), now)
+    }
+
+    fn update_with(&mut self, neighbor: Neighbor, received: Option<&Received>, now: SystemTime) -> Option<DiscoveryEvent> {
+        let mac = neighbor.mac_address?;
+        let vlan = received.and_then(|received| received.vlan);
+        let scope_id = received.and_then(|received| received.scope_id);
+
+        let entry = match self.entries.get_mut(&mac) {
+            Some(entry) => entry,
+            None => {
+                let entry = DiscoveredNeighbor {
+                    neighbor,
+                    first_seen: now,
+                    last_seen: now,
+                    vlan,
+                    scope_id,
+                };
+                self.entries.insert(mac, entry.clone());
+                return Some(DiscoveryEvent::Added(entry));
+            }
+        };
+
+        let rebooted = match (entry.neighbor.uptime, neighbor.uptime) {
+            (Some(old), Some(new)) => new < old,
+            _ => false,
+        };
+        let mut changed = entry.neighbor.changed_fields(&neighbor);
+        changed.retain(|field| *field != NeighborField::Uptime);
+
+        entry.neighbor = neighbor;
+        entry.last_seen = now;
+        entry.vlan = vlan;
+        entry.scope_id = scope_id;
+
+        if rebooted {
+            Some(DiscoveryEvent::Rebooted(entry.clone()))
+        } else if !changed.is_empty() {
+            Some(DiscoveryEvent::Updated { neighbor: entry.clone(), changed })
+        } else {
+            None
+        }
+    }
+
+    /// Insert an entry as-is, replacing any existing entry with the same MAC
+    /// address. Entries without a MAC address are ignored.
+    pub fn insert(&mut self, entry: DiscoveredNeighbor) {
+        if let Some(mac) = entry.neighbor.mac_address {
+            self.entries.insert(mac, entry);
+        }
+    }
+
+    /// Remove a neighbor from the table, returning it if present.
+    pub fn remove(&mut self, mac: &MacAddr6) -> Option<DiscoveredNeighbor> {
+        self.entries.remove(mac)
+    }
+
+    /// Remove all neighbors not heard from within the TTL as of `now`,
+    /// returning an `Expired` event for each.
+    pub fn expire(&mut self, now: SystemTime) -> Vec<DiscoveryEvent> {
+        let ttl = self.ttl;
+        let stale: Vec<MacAddr6> = self.entries.iter()
+            .filter(|(_, entry)| match now.duration_since(entry.last_seen) {
+                Ok(age) => age > ttl,
+                Err(_) => false,
+            })
+            .map(|(mac, _)| *mac)
+            .collect();
+
+        stale.iter()
+            .filter_map(|mac| self.entries.remove(mac))
+            .map(DiscoveryEvent::Expired)
+            .collect()
+    }
+
+    /// Write the table, including timestamps, to `writer`.
+    ///
+    /// Each neighbor is stored as an MNDP packet so that the file format
+    /// follows the protocol as new fields are supported.
+    pub fn save<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = writer;
+        writer.write_all(TABLE_MAGIC)?;
+        writer.write_all(&TABLE_VERSION.to_be_bytes())?;
+
+        for entry in self.entries.values() {
+            write_time(&mut writer, entry.first_seen)?;
+            write_time(&mut writer, entry.last_seen)?;
+
+            let bytes: Bytes = Packet::from_neighbor(&entry.neighbor).to_bytes();
+            writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+            writer.write_all(&bytes)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Read entries previously written by `save` from `reader` into the table.
+    ///
+    /// Loaded entries keep their original timestamps, so stale neighbors are
+    /// removed by the next call to `expire`. An entry replaces an existing
+    /// one only if it was seen more recently.
+    pub fn load<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let mut reader = reader;
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != TABLE_MAGIC {
+            return Err(invalid_data("not a neighbor table file"));
+        }
+
+        let mut version = [0u8; 2];
+        reader.read_exact(&mut version)?;
+        if u16::from_be_bytes(version) != TABLE_VERSION {
+            return Err(invalid_data("unsupported neighbor table version"));
+        }
+
+        // Read entries until a clean EOF at an entry boundary
+        loop {
+            let first_seen = match read_time(&mut reader) {
+                Ok(time) => time,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            let last_seen = read_time(&mut reader)?;
+
+            // Bounded before allocating, as the file may be corrupt
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            let len = u32::from_be_bytes(len) as usize;
+            if len > DEFAULT_MAX_PACKET_SIZE {
+                return Err(invalid_data("oversized packet in neighbor table"));
+            }
+            let mut buf = vec![0u8; len];
+            reader.read_exact(&mut buf)?;
+
+            let packet = Packet::from_bytes(buf)
+                .map_err(|_| invalid_data("invalid packet in neighbor table"))?;
+            let entry = DiscoveredNeighbor {
+                neighbor: packet.to_neighbor(),
+                first_seen,
+                last_seen,
+                vlan: None,
+                scope_id: None,
+            };
+
+            let newer = match entry.neighbor.mac_address.and_then(|mac| self.entries.get(&mac)) {
+                Some(existing) => entry.last_seen > existing.last_seen,
+                None => true,
+            };
+            if newer {
+                self.insert(entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Save the table to a file at `path`, replacing it if it exists.
+    ///
+    /// The table is written to `path` with `.tmp` appended, synced, and
+    /// renamed over `path`, so an interrupted save leaves the previous file
+    /// intact.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        let result = File::create(&temp).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            self.save(&mut writer)?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            fs::rename(&temp, path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Load entries into the table from a file at `path`.
+    pub fn load_from_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Serialize the entries as a JSON array, sorted by MAC address.
+    #[cfg(feature = "json")]
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        let mut entries: Vec<&DiscoveredNeighbor> = self.iter().collect();
+        entries.sort_by_key(|entry| entry.neighbor.mac_address);
+        serde_json::to_string(&entries)
+    }
+
+    /// Deserialize a table from a JSON array of entries, as written by
+    /// `to_json_string`, using `DEFAULT_TTL`.
+    #[cfg(feature = "json")]
+    pub fn from_json_str(json: &str) -> serde_json::Result<NeighborTable> {
+        let entries: Vec<DiscoveredNeighbor> = serde_json::from_str(json)?;
+        let mut table = NeighborTable::new();
+        for entry in entries {
+            table.insert(entry);
+        }
+        Ok(table)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Times are stored as seconds (u64) and nanoseconds (u32) since the Unix epoch
+fn write_time<W: Write>(writer: &mut W, time: SystemTime) -> io::Result<()> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    writer.write_all(&since_epoch.as_secs().to_be_bytes())?;
+    writer.write_all(&since_epoch.subsec_nanos().to_be_bytes())
+}
+
+fn read_time<R: Read>(reader: &mut R) -> io::Result<SystemTime> {
+    let mut secs = [0u8; 8];
+    let mut nanos = [0u8; 4];
+    reader.read_exact(&mut secs)?;
+    reader.read_exact(&mut nanos)?;
+    let nanos = u32::from_be_bytes(nanos);
+    if nanos >= 1_000_000_000 {
+        return Err(invalid_data("invalid time in neighbor table"));
+    }
+    UNIX_EPOCH.checked_add(Duration::new(u64::from_be_bytes(secs), nanos))
+        .ok_or_else(|| invalid_data("invalid time in neighbor table"))
+}
+
+#[test]
+fn test_table_save_load() {
+    let now = SystemTime::now();
+    let mut table = NeighborTable::new();
+    table.update(Neighbor::builder()
+        .mac_address([0xc4, 0xad, 0x34, 0xbf, 0x91, 0x11])
+        .identity("router1")
+        .uptime(Duration::from_secs(3600))
+        .build(), now - Duration::from_secs(30));
+    table.update(Neighbor::builder()
+        .mac_address([0xc4, 0xad, 0x34, 0xbf, 0x91, 0x12])
+        .identity("switch1")
+        .ipv4_address([192, 168, 88, 1])
+        .build(), now);
+
+    let mut buf = Vec::new();
+    table.save(&mut buf).unwrap();
+
+    let mut loaded = NeighborTable::new();
+    loaded.load(buf.as_slice()).unwrap();
+    assert_eq!(loaded.len(), 2);
+    for entry in table.iter() {
+        assert_eq!(loaded.get(&entry.neighbor.mac_address.unwrap()), Some(entry));
+    }
+}
+
+#[test]
+fn test_table_save_to_path() {
+    let path = std::env::temp_dir().join(format!("mndp-table-{}.state", std::process::id()));
+    let mut table = NeighborTable::new();
+    table.update(Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).build(), SystemTime::now());
+    table.save_to_path(&path).unwrap();
+    table.save_to_path(&path).unwrap();
+
+    // The file is replaced by renaming, leaving no temporary file
+    let mut loaded = NeighborTable::new();
+    loaded.load_from_path(&path).unwrap();
+    assert_eq!(loaded.len(), 1);
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    assert!(!Path::new(&temp).exists());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_table_load_invalid() {
+    let header = [&TABLE_MAGIC[..], &TABLE_VERSION.to_be_bytes()].concat();
+    let load = |entry: &[u8]| NeighborTable::new().load([&header[..], entry].concat().as_slice());
+
+    // A corrupt length is rejected rather than allocated
+    let oversized = [&[0u8; 24][..], &u32::MAX.to_be_bytes()].concat();
+    assert_eq!(load(&oversized).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    // Nanoseconds must be below a second, and the time representable
+    let nanos = [&[0u8; 8][..], &1_000_000_000u32.to_be_bytes()].concat();
+    assert_eq!(load(&nanos).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let overflow = [&u64::MAX.to_be_bytes()[..], &[0u8; 4]].concat();
+    assert_eq!(load(&overflow).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_table_expire() {
+    let now = SystemTime::now();
+    let mut table = NeighborTable::with_ttl(Duration::from_secs(60));
+    assert!(table.update(Neighbor::builder().identity("no-mac").build(), now).is_none());
+    table.update(Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).build(), now - Duration::from_secs(120));
+    table.update(Neighbor::builder().mac_address([0, 0, 0, 0, 0, 2]).build(), now - Duration::from_secs(30));
+
+    let expired = table.expire(now);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].neighbor().neighbor.mac_address, Some([0, 0, 0, 0, 0, 1].into()));
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+fn test_table_update_events() {
+    let now = SystemTime::now();
+    let mut table = NeighborTable::new();
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .version("6.48.1 (stable)")
+        .uptime(Duration::from_secs(100));
+
+    let event = table.update(neighbor.clone().build(), now);
+    assert!(matches!(event, Some(DiscoveryEvent::Added(_))));
+
+    // Only the uptime changed
+    let event = table.update(neighbor.clone().uptime(Duration::from_secs(160)).build(), now);
+    assert_eq!(event, None);
+
+    let event = table.update(neighbor.clone().uptime(Duration::from_secs(220)).version("7.1").build(), now);
+    assert_eq!(event.unwrap().changed(), &[NeighborField::Version]);
+
+    let event = table.update(neighbor.uptime(Duration::from_secs(5)).version("7.1").build(), now);
+    assert!(matches!(event, Some(DiscoveryEvent::Rebooted(_))));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_table_json() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let mut table = NeighborTable::new();
+    table.update(Neighbor::builder().mac_address([0, 0, 0, 0, 0, 2]).identity("b").build(), now);
+    table.update(Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).identity("a").build(), now);
+
+    let json = table.to_json_string().unwrap();
+    assert!(json.find("\"a\"").unwrap() < json.find("\"b\"").unwrap());
+
+    let loaded = NeighborTable::from_json_str(&json).unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.get(&[0, 0, 0, 0, 0, 1].into()), table.get(&[0, 0, 0, 0, 0, 1].into()));
+
+    let neighbor = Neighbor::from_json_str(r#"{"identity": "c"}"#).unwrap();
+    assert_eq!(neighbor, Neighbor::builder().identity("c").build());
+    assert_eq!(Neighbor::from_json_str(&neighbor.to_json_string().unwrap()).unwrap(), neighbor);
+}
+
+#[test]
+fn test_ipv6_socket_addr() {
+    use std::net::SocketAddr;
+
+    let mut table = NeighborTable::new();
+    let neighbor = Neighbor::builder()
+        .mac_address([0, 0, 0, 0, 0, 1])
+        .ipv6_address("fe80::1".parse::<std::net::Ipv6Addr>().unwrap())
+        .build();
+    let received = Received {
+        len: 0,
+        source: "[fe80::1%3]:5678".parse().unwrap(),
+        vlan: None,
+        scope_id: Some(3),
+        link_source: None,
+    };
+    let discovered = table.update_received(neighbor.clone(), &received, UNIX_EPOCH).unwrap().neighbor().clone();
+    assert_eq!(discovered.scope_id, Some(3));
+    assert_eq!(discovered.ipv6_socket_addr(22).map(SocketAddr::V6), Some("[fe80::1%3]:22".parse().unwrap()));
+
+    // A link-local address is not connectable without its zone
+    let unscoped = DiscoveredNeighbor { scope_id: None, ..discovered.clone() };
+    assert_eq!(unscoped.ipv6_socket_addr(22), None);
+
+    // The zone is not needed for a global address
+    let mut global = unscoped;
+    global.neighbor.ipv6_address = Some("2001:db8::1".parse().unwrap());
+    assert_eq!(global.ipv6_socket_addr(22).unwrap().scope_id(), 0);
+}
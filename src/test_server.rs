@@ -0,0 +1,44 @@
+//! HTTP server for testing the HTTP clients, such as `Webhook` and `NetBox`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+// Listen on a local port and answer one connection with each of `responses`
+// in turn, as a status and body, then stop. Returns the server's base URL
+// and a thread returning each request's request line and body.
+pub(crate) fn serve(responses: &[(&str, &str)]) -> (String, JoinHandle<Vec<(String, String)>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let responses: Vec<(String, String)> = responses.iter()
+        .map(|(status, body)| (status.to_string(), body.to_string()))
+        .collect();
+
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, response) in &responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    len = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, response.len(), response).unwrap();
+            requests.push((request_line.trim_end().to_string(), String::from_utf8(body).unwrap()));
+        }
+        requests
+    });
+    (url, server)
+}
@@ -0,0 +1,166 @@
+//! Webhook sink which POSTs discovery events as JSON to a URL.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::DiscoveryEvent;
+
+/// Sink which delivers `DiscoveryEvent`s to an HTTP(S) endpoint.
+///
+/// Events are queued and sent in order from a background thread, so
+/// `notify` never blocks on the network. Failed deliveries are retried with
+/// exponential backoff and dropped once the retry limit is reached. Dropping
+/// the `Webhook` waits for queued events to be delivered or given up on.
+#[derive(Debug)]
+pub struct Webhook {
+    sender: Option<Sender<DiscoveryEvent>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Builder structure for a `Webhook`.
+#[derive(Clone, Debug)]
+pub struct WebhookBuilder {
+    url: String,
+    headers: Vec<(String, String)>,
+    retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    timeout: Duration,
+}
+
+impl Webhook {
+    /// Create a new webhook builder which will POST to `url`.
+    pub fn builder<S: Into<String>>(url: S) -> WebhookBuilder {
+        WebhookBuilder {
+            url: url.into(),
+            headers: Vec::new(),
+            retries: 5,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Queue an event for delivery.
+    pub fn notify(&self, event: &DiscoveryEvent) {
+        if let Some(sender) = &self.sender {
+            // The worker only exits once the sender is dropped
+            let _ = sender.send(event.clone());
+        }
+    }
+}
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl WebhookBuilder {
+    /// Add an HTTP header sent with every request; e.g. for authorization.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the number of retries after a failed delivery (default 5).
+    pub fn retries(mut self, value: u32) -> Self {
+        self.retries = value;
+        self
+    }
+
+    /// Set the delay before the first retry, doubled on each further retry
+    /// (default 1 second).
+    pub fn backoff<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.backoff = value.into();
+        self
+    }
+
+    /// Set the upper limit on the delay between retries (default 60 seconds).
+    pub fn max_backoff<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.max_backoff = value.into();
+        self
+    }
+
+    /// Set the timeout for each request (default 10 seconds).
+    pub fn timeout<D: Into<Duration>>(mut self, value: D) -> Self {
+        self.timeout = value.into();
+        self
+    }
+
+    /// Start the delivery thread and return the finished `Webhook`.
+    pub fn build(self) -> Webhook {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || self.run(receiver));
+        Webhook {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    fn run(self, receiver: Receiver<DiscoveryEvent>) {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(self.timeout)
+            .build();
+
+        for event in receiver {
+            let body = match serde_json::to_string(&event) {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+
+            let mut delay = self.backoff;
+            for attempt in 0..=self.retries {
+                if attempt > 0 {
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(self.max_backoff);
+                }
+
+                let mut request = agent.post(&self.url)
+                    .set("Content-Type", "application/json");
+                for (name, value) in &self.headers {
+                    request = request.set(name, value);
+                }
+
+                match request.send_string(&body) {
+                    Ok(_) => break,
+                    // Client errors other than rate limiting won't succeed on retry
+                    Err(ureq::Error::Status(code, _)) if code < 500 && code != 429 => break,
+                    Err(_) => continue,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_webhook_retry() {
+    use std::time::SystemTime;
+    use crate::{DiscoveredNeighbor, Neighbor};
+
+    // Fail the first request, then accept the second
+    let (url, server) = crate::test_server::serve(&[("500 Internal Server Error", ""), ("200 OK", "")]);
+    let url = format!("{}hook", url);
+
+    let webhook = Webhook::builder(url).backoff(Duration::from_millis(10)).build();
+    webhook.notify(&DiscoveryEvent::Added(DiscoveredNeighbor {
+        neighbor: Neighbor::builder().mac_address([0, 0, 0, 0, 0, 1]).identity("router1").build(),
+        first_seen: SystemTime::now(),
+        last_seen: SystemTime::now(),
+        vlan: None,
+        scope_id: None,
+    }));
+    drop(webhook);
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0], requests[1]);
+    assert_eq!(requests[1].0, "POST /hook HTTP/1.1");
+    let body: serde_json::Value = serde_json::from_str(&requests[1].1).unwrap();
+    assert_eq!(body["event"], "added");
+    assert_eq!(body["neighbor"]["mac_address"], "00:00:00:00:00:01");
+    assert_eq!(body["neighbor"]["identity"], "router1");
+}